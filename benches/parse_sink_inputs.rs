@@ -0,0 +1,66 @@
+//! `cargo bench --bench parse_sink_inputs`: parsing a large `pactl list
+//! sink-inputs` output (the UI does this every second).
+
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+
+// The crate is a binary only; pull the parser in directly, with the command
+// runner it uses.
+#[allow(dead_code)]
+#[path = "../src/pactl.rs"]
+mod pactl;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/exec.rs"]
+mod exec;
+
+const STREAMS: u32 = 60;
+const EXTRA_PROPERTIES: u32 = 40;
+
+/// `STREAMS` entries shaped like real pipewire-pulse output, each with the
+/// usual fields and `EXTRA_PROPERTIES` more properties.
+fn fixture() -> String {
+    let mut out = String::new();
+    for index in 0..STREAMS {
+        out.push_str(&format!(
+            "Sink Input #{index}\n\
+             \tDriver: PipeWire\n\
+             \tOwner Module: n/a\n\
+             \tClient: {client}\n\
+             \tSink: 57\n\
+             \tSample Specification: float32le 2ch 48000Hz\n\
+             \tChannel Map: front-left,front-right\n\
+             \tFormat: pcm, format.sample_format = \"\\\"float32le\\\"\"  format.rate = \"48000\"  format.channels = \"2\"\n\
+             \tCorked: no\n\
+             \tMute: no\n\
+             \tVolume: front-left: 42597 /  65% / -11.23 dB,   front-right: 42597 /  65% / -11.23 dB\n\
+             \t        balance 0.00\n\
+             \tBuffer Latency: 0 usec\n\
+             \tSink Latency: 0 usec\n\
+             \tResample method: PipeWire\n\
+             \tProperties:\n\
+             \t\tmedia.name = \"Playback stream {index}\"\n\
+             \t\tapplication.name = \"App {index}\"\n\
+             \t\tapplication.process.binary = \"app{index}\"\n\
+             \t\tapplication.process.id = \"{pid}\"\n\
+             \t\tobject.id = \"{object}\"\n\
+             \t\tnode.latency = \"1024/48000\"\n",
+            client = 100 + index,
+            pid = 4000 + index,
+            object = 200 + index,
+        ));
+        for prop in 0..EXTRA_PROPERTIES {
+            out.push_str(&format!("\t\tsome.extra.property{prop} = \"value {prop} of stream {index}\"\n"));
+        }
+    }
+    out
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let stdout = fixture();
+    assert_eq!(pactl::parse_sink_inputs_output(&stdout).len(), STREAMS as usize);
+    c.bench_function("parse_sink_inputs_output (60 streams)", |b| {
+        b.iter(|| pactl::parse_sink_inputs_output(black_box(&stdout)))
+    });
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
@@ -0,0 +1,100 @@
+//! The desktop's accent color, from the XDG settings portal
+//! (`org.freedesktop.appearance` `accent-color`, read with `gdbus`).
+//!
+//! The query runs once on a background thread; desktops without the portal
+//! or without an accent simply report none and the built-in accent stays.
+
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use eframe::egui;
+
+pub struct SystemAccent {
+    color: Arc<Mutex<Option<[u8; 3]>>>,
+}
+
+impl SystemAccent {
+    /// Start the lookup; `ctx` is repainted once it has an answer.
+    pub fn new(ctx: &egui::Context) -> Self {
+        let color = Arc::new(Mutex::new(None));
+        {
+            let color = Arc::clone(&color);
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                let Some(found) = read_portal() else {
+                    return;
+                };
+                if let Ok(mut color) = color.lock() {
+                    *color = Some(found);
+                }
+                ctx.request_repaint();
+            });
+        }
+        Self { color }
+    }
+
+    /// The accent (sRGB), once known.
+    pub fn get(&self) -> Option<[u8; 3]> {
+        *self.color.lock().ok()?
+    }
+}
+
+fn read_portal() -> Option<[u8; 3]> {
+    let output = Command::new("gdbus")
+        .args([
+            "call",
+            "--session",
+            "--timeout",
+            "2",
+            "--dest",
+            "org.freedesktop.portal.Desktop",
+            "--object-path",
+            "/org/freedesktop/portal/desktop",
+            "--method",
+            "org.freedesktop.portal.Settings.Read",
+            "org.freedesktop.appearance",
+            "accent-color",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_portal_color(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The `(ddd)` tuple in a `gdbus` reply such as
+/// `(<<(0.21, 0.52, 0.89)>>,)`. The spec says values outside 0..=1 mean
+/// "no accent set".
+fn parse_portal_color(text: &str) -> Option<[u8; 3]> {
+    let start = text.rfind('(')?;
+    let end = start + text[start..].find(')')?;
+    let values: Vec<f32> = text[start + 1..end]
+        .split(',')
+        .map(|v| v.trim().parse::<f32>())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    let [r, g, b] = <[f32; 3]>::try_from(values).ok()?;
+    if [r, g, b].iter().any(|v| !(0.0..=1.0).contains(v)) {
+        return None;
+    }
+    Some([r, g, b].map(|v| (v * 255.0).round() as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_portal_replies() {
+        assert_eq!(
+            parse_portal_color("(<<(0.21176470816135406, 0.51764708757400513, 0.89411765336990356)>>,)\n"),
+            Some([54, 132, 228])
+        );
+        assert_eq!(parse_portal_color("(<(1.0, 0.0, 0.5)>,)"), Some([255, 0, 128]));
+        // Unset, and not a color at all.
+        assert_eq!(parse_portal_color("(<<(-1.0, -1.0, -1.0)>>,)"), None);
+        assert_eq!(parse_portal_color("(<<uint32 0>>,)"), None);
+    }
+}
@@ -0,0 +1,127 @@
+//! Battery levels of Bluetooth audio devices, read from UPower (`upower -d`).
+//!
+//! A background thread polls once a minute; sinks are matched to devices by
+//! the Bluetooth address in their name (`bluez_output.AA_BB_CC_DD_EE_FF.1`).
+//! Without `upower`, or for wired devices, there's simply no level.
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct Batteries {
+    levels: Arc<Mutex<HashMap<String, f32>>>, // address (AA_BB_...) -> percent
+}
+
+impl Batteries {
+    pub fn new() -> Self {
+        let levels = Arc::new(Mutex::new(HashMap::new()));
+        {
+            let levels = Arc::clone(&levels);
+            thread::spawn(move || loop {
+                let Ok(output) = Command::new("upower").arg("-d").output() else {
+                    break; // not installed
+                };
+                let parsed = parse_upower_dump(&String::from_utf8_lossy(&output.stdout));
+                match levels.lock() {
+                    Ok(mut levels) => *levels = parsed,
+                    Err(_) => break,
+                }
+                thread::sleep(POLL_INTERVAL);
+            });
+        }
+        Self { levels }
+    }
+
+    /// Battery percentage of the device behind sink `sink_name`, if known.
+    pub fn for_sink(&self, sink_name: &str) -> Option<f32> {
+        let address = sink_address(sink_name)?;
+        self.levels.lock().ok()?.get(address).copied()
+    }
+}
+
+/// The Bluetooth address part of a BlueZ sink name, in the underscore form
+/// used by both PipeWire (`bluez_output.`) and PulseAudio (`bluez_sink.`).
+fn sink_address(sink_name: &str) -> Option<&str> {
+    let rest = sink_name
+        .strip_prefix("bluez_output.")
+        .or_else(|| sink_name.strip_prefix("bluez_sink."))?;
+    Some(rest.split('.').next().unwrap_or(rest))
+}
+
+/// Percentages per Bluetooth address from `upower -d`. Devices are blocks
+/// starting with `Device:`; BlueZ ones carry a `native-path` ending in
+/// `dev_AA_BB_CC_DD_EE_FF`.
+fn parse_upower_dump(text: &str) -> HashMap<String, f32> {
+    let mut levels = HashMap::new();
+    let mut address: Option<String> = None;
+
+    for line in text.lines() {
+        if line.starts_with("Device:") || line.starts_with("Daemon:") {
+            address = None;
+            continue;
+        }
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key {
+            "native-path" => {
+                address = value.rsplit('/').next().and_then(|p| p.strip_prefix("dev_")).map(str::to_string);
+            }
+            "percentage" => {
+                if let Some(address) = &address
+                    && let Ok(percent) = value.trim_end_matches('%').trim().parse::<f32>()
+                {
+                    levels.insert(address.clone(), percent);
+                }
+            }
+            _ => {}
+        }
+    }
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = "Device: /org/freedesktop/UPower/devices/battery_BAT0
+  native-path:          BAT0
+  vendor:               SMP
+  power supply:         yes
+  battery
+    state:               discharging
+    percentage:          81%
+
+Device: /org/freedesktop/UPower/devices/headset_dev_38_18_4C_12_AB_CD
+  native-path:          /org/bluez/hci0/dev_38_18_4C_12_AB_CD
+  model:                WH-1000XM4
+  power supply:         no
+  headset
+    warning-level:       none
+    percentage:          70%
+    icon-name:          'battery-full-symbolic'
+
+Daemon:
+  daemon-version:  1.90.2
+";
+
+    #[test]
+    fn reads_bluetooth_levels_only() {
+        let levels = parse_upower_dump(DUMP);
+
+        assert_eq!(levels.len(), 1);
+        assert_eq!(levels.get("38_18_4C_12_AB_CD"), Some(&70.0));
+    }
+
+    #[test]
+    fn finds_the_address_in_sink_names() {
+        assert_eq!(sink_address("bluez_output.38_18_4C_12_AB_CD.1"), Some("38_18_4C_12_AB_CD"));
+        assert_eq!(sink_address("bluez_sink.38_18_4C_12_AB_CD.a2dp_sink"), Some("38_18_4C_12_AB_CD"));
+        assert_eq!(sink_address("alsa_output.pci-0000_00_1f.3.analog-stereo"), None);
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::pactl::{self, Sink, VolumeError};
+
+/// Plays single apps on every output at once by giving each one its own
+/// `module-combine-sink` over all real sinks.
+///
+/// The commands run in jobs for the command worker (`exec`); `active` is only
+/// locked around reads and updates, never over a command.
+pub struct Broadcast {
+    active: Arc<Mutex<HashMap<u32, Active>>>, // sink-input index -> combine sink it plays on
+}
+
+struct Active {
+    module: u32,
+    original_sink: String,
+}
+
+impl Broadcast {
+    pub fn new() -> Self {
+        Self {
+            active: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn is_active(&self, index: u32) -> bool {
+        lock(&self.active).contains_key(&index)
+    }
+
+    /// The job that starts playing `index` on all of `sinks`. `current_sink`
+    /// is where it plays now and where `stop` sends it back to.
+    pub fn start(
+        &self,
+        index: u32,
+        current_sink: &str,
+        sinks: &[Sink],
+    ) -> impl FnOnce() -> Result<(), VolumeError> + Send + 'static {
+        let active = Arc::clone(&self.active);
+        let current_sink = current_sink.to_string();
+        let slaves: Vec<String> = sinks
+            .iter()
+            .map(|s| s.name.clone())
+            .filter(|name| !name.starts_with(pactl::OWN_SINK_PREFIX))
+            .collect();
+        move || {
+            if lock(&active).contains_key(&index) || slaves.len() < 2 {
+                return Ok(());
+            }
+
+            let name = format!("{}all_{}", pactl::OWN_SINK_PREFIX, index);
+            let args = [
+                format!("sink_name={}", name),
+                format!("slaves={}", slaves.join(",")),
+                "sink_properties=device.description=\"All outputs\"".to_string(),
+            ];
+            let module = pactl::load_module("module-combine-sink", &args)?;
+            if let Err(e) = pactl::move_sink_input(index, &name) {
+                let _ = pactl::unload_module(module);
+                return Err(e);
+            }
+            lock(&active).insert(
+                index,
+                Active {
+                    module,
+                    original_sink: current_sink,
+                },
+            );
+            Ok(())
+        }
+    }
+
+    /// The job that sends `index` back to its original sink and drops its
+    /// combine sink.
+    pub fn stop(&self, index: u32) -> impl FnOnce() -> Result<(), VolumeError> + Send + 'static {
+        let active = Arc::clone(&self.active);
+        move || stop(&active, index)
+    }
+
+    /// Forget streams that have gone away. Returns the job that unloads their
+    /// combine sinks, if any.
+    pub fn retain_streams(&self, alive: impl Fn(u32) -> bool) -> Option<impl FnOnce() + Send + 'static> {
+        let mut active = lock(&self.active);
+        let gone: Vec<u32> = active.keys().copied().filter(|index| !alive(*index)).collect();
+        let modules: Vec<u32> = gone.iter().filter_map(|index| active.remove(index)).map(|a| a.module).collect();
+        (!modules.is_empty()).then_some(move || {
+            for module in modules {
+                // Logged; the stream it was for is gone anyway.
+                let _ = pactl::unload_module(module);
+            }
+        })
+    }
+
+    /// The job that stops every stream, for the way out.
+    pub fn cleanup(&self) -> impl FnOnce() + Send + 'static {
+        let active = Arc::clone(&self.active);
+        move || {
+            let indices: Vec<u32> = lock(&active).keys().copied().collect();
+            for index in indices {
+                let _ = stop(&active, index);
+            }
+        }
+    }
+}
+
+fn stop(active: &Mutex<HashMap<u32, Active>>, index: u32) -> Result<(), VolumeError> {
+    let Some(active) = lock(active).remove(&index) else {
+        return Ok(());
+    };
+    let moved = pactl::move_sink_input(index, &active.original_sink);
+    moved.and(pactl::unload_module(active.module))
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,626 @@
+//! User configuration, persisted as TOML under `$XDG_CONFIG_HOME`.
+//!
+//! The file carries a schema `version`. Older files are migrated in place on
+//! load (the original is kept next to it as `config.toml.v<N>.bak`); files
+//! from a newer release are left untouched and the defaults are used instead.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::pactl;
+
+/// Schema version written by this build.
+pub const CURRENT_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub version: u32,
+    /// Friendly names for sinks, keyed by the sink's stable node name
+    /// (e.g. `alsa_output.pci-0000_00_1f.3.analog-stereo` -> "Desk Speakers").
+    pub sink_names: BTreeMap<String, String>,
+    /// Never offer to remember a volume after the user sets one.
+    pub suppress_remember_prompt: bool,
+    /// Only ever send whole percentages to the sound server.
+    pub integer_volumes: bool,
+    /// How a multi-channel volume is shown as one value.
+    pub channel_mode: pactl::ChannelMode,
+    /// Explain each slider's value with pactl's raw per-channel volumes on
+    /// hover (advanced).
+    pub show_raw_volumes: bool,
+    /// Ask before muting an app that is playing music or video.
+    pub confirm_mute_playing: bool,
+    /// Hold app volumes above 100% coming from saved volumes or scenes at
+    /// 100% until confirmed.
+    pub confirm_overboost: bool,
+    /// Show each app's effective level (app volume × output volume).
+    pub show_effective_volume: bool,
+    /// Under each app's meter, a second bar of its peak scaled by its volume.
+    pub show_loudness_bars: bool,
+    /// Volume every system sound (event) stream is set to when it appears;
+    /// None leaves them alone.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_sounds_volume: Option<f32>,
+    /// Keep the volume media keys grabbed while the window isn't focused.
+    pub global_media_keys: bool,
+    /// Global key that mutes everything, and restores it on a second press.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub panic_key: Option<String>,
+    /// A tray click opens the compact popup rather than the main window.
+    pub tray_popup: bool,
+    /// Start with only the tray icon, e.g. when autostarted.
+    pub start_hidden: bool,
+    /// What the window does once the last app stops playing.
+    pub when_no_apps: NoAppsAction,
+    /// Meter the default output's monitor source under the system slider.
+    pub show_monitor_level: bool,
+    /// Microphone mute state to put back at startup; None leaves the
+    /// microphone as it is.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mic_muted: Option<bool>,
+    /// Use the desktop's accent color (XDG settings portal) when it has one.
+    pub follow_system_accent: bool,
+    /// Accent color (sRGB) chosen here; wins over the desktop's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accent_color: Option<[u8; 3]>,
+    /// Show the floating "what's making sound" window.
+    pub activity_hud: bool,
+    /// List apps as read-only rows (name, volume, mute, meter) for
+    /// monitoring; clicking one opens its controls.
+    pub compact_apps: bool,
+    /// Show the title of each app's window (the focused one if it has
+    /// several), polled from the window manager.
+    pub show_window_titles: bool,
+    /// Let app groups collapse to just their name.
+    pub collapsible_app_groups: bool,
+    /// With apps on more than one output, list them under a collapsible
+    /// header per output.
+    pub group_apps_by_output: bool,
+    /// Expand a collapsed app group when the app starts making sound.
+    pub auto_expand_active: bool,
+    /// Collapse an auto-expanded group again once the app has gone quiet.
+    pub auto_collapse_idle: bool,
+    /// Also list (read-only) streams of other sessions' sound servers.
+    pub show_other_sessions: bool,
+    /// Serve the line-based control socket (see `control`).
+    pub control_socket: bool,
+    /// Shell command run on volume events (see `hooks`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hook_command: Option<String>,
+    /// Volume (percent) whose crossing fires `volume-above`/`volume-below`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hook_volume_threshold: Option<f32>,
+    /// Start with the mixer locked read-only.
+    pub lock_on_start: bool,
+    /// PIN needed to unlock. Only guards against accidental changes: it is
+    /// stored as plain text in this file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lock_pin: Option<String>,
+    /// User-made groups of apps (by app name) that share one slider.
+    pub custom_groups: BTreeMap<String, Vec<String>>,
+    /// Named routing scenes, restorable in one click.
+    pub scenes: BTreeMap<String, Scene>,
+    /// Stream properties tried in order for an app's label.
+    pub label_properties: Vec<String>,
+    /// User-defined shortcuts (see `keybindings`).
+    pub keybindings: Vec<KeybindingConfig>,
+    /// "Mute this app while that one plays" rules.
+    pub auto_mute: Vec<AutoMuteRule>,
+    /// Per-app settings, keyed by app name.
+    pub apps: BTreeMap<String, AppConfig>,
+    /// Write changes at most this often (seconds), so dragging a slider
+    /// doesn't rewrite the file every frame. 0 writes immediately.
+    pub save_interval_secs: f32,
+    /// Unanswered "Sure?" prompts of destructive actions cancel themselves
+    /// after this many seconds. 0 waits forever.
+    pub confirm_timeout_secs: f32,
+    /// Leave at least this many milliseconds between two sound server
+    /// commands; a dragged slider sends only its latest volume. 0: no limit.
+    pub min_command_interval_ms: u32,
+
+    /// Why the file on disk wasn't used, if it wasn't. Saving is disabled
+    /// while this is set so a newer config is never clobbered.
+    #[serde(skip)]
+    pub load_error: Option<String>,
+    /// Changed since the last write (see `save_later`).
+    #[serde(skip)]
+    dirty: bool,
+    #[serde(skip)]
+    last_save: Option<Instant>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct AppConfig {
+    /// Volume (percent) new streams of the app start at.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+    /// Volumes for specific output devices (by sink name), used instead of
+    /// `volume` while the app plays there.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub devices: BTreeMap<String, f32>,
+    /// Color tag (sRGB) shown as a stripe along the app's group.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<[u8; 3]>,
+    /// Mute the app's streams along with the system, for apps that play on
+    /// through a muted output.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub follow_system_mute: bool,
+}
+
+/// What the window does when the last app closes. Undone as soon as an app
+/// plays again.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NoAppsAction {
+    #[default]
+    StayOpen,
+    /// Hide to the tray (only with one to come back from).
+    HideToTray,
+    /// Fold everything below the system slider away.
+    SystemOnly,
+}
+
+impl NoAppsAction {
+    pub const ALL: [Self; 3] = [Self::StayOpen, Self::HideToTray, Self::SystemOnly];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::StayOpen => "Stay as it is",
+            Self::HideToTray => "Hide to the tray",
+            Self::SystemOnly => "Show only the system slider",
+        }
+    }
+}
+
+/// One `[[keybindings]]` entry. Kept as text so a typo is reported in the UI
+/// instead of making the whole file unreadable.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct KeybindingConfig {
+    /// e.g. `Ctrl+Shift+M`.
+    pub key: String,
+    /// e.g. `toggle-mute-app`.
+    pub action: String,
+    /// App name for the `*-app` actions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+}
+
+/// Mute `mute` while `when` plays, e.g. music during a call. Both are app
+/// names (or binaries), matched ignoring case.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct AutoMuteRule {
+    pub mute: String,
+    pub when: String,
+}
+
+/// A saved routing layout: the default output, card profiles, and where and
+/// how loud each app plays.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Scene {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_sink: Option<String>,
+    /// Card name -> profile name.
+    pub cards: BTreeMap<String, String>,
+    /// Keyed by app name, like `apps`.
+    pub apps: BTreeMap<String, SceneApp>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SceneApp {
+    /// Sink name the app plays on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sink: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+    pub muted: bool,
+}
+
+impl AppConfig {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            sink_names: BTreeMap::new(),
+            suppress_remember_prompt: false,
+            integer_volumes: false,
+            channel_mode: pactl::ChannelMode::Average,
+            show_raw_volumes: false,
+            confirm_mute_playing: false,
+            confirm_overboost: true,
+            show_effective_volume: false,
+            show_loudness_bars: false,
+            system_sounds_volume: None,
+            global_media_keys: false,
+            panic_key: None,
+            tray_popup: true,
+            start_hidden: false,
+            when_no_apps: NoAppsAction::StayOpen,
+            show_monitor_level: false,
+            mic_muted: None,
+            follow_system_accent: true,
+            accent_color: None,
+            activity_hud: false,
+            compact_apps: false,
+            show_window_titles: false,
+            collapsible_app_groups: false,
+            group_apps_by_output: false,
+            auto_expand_active: false,
+            auto_collapse_idle: false,
+            show_other_sessions: false,
+            control_socket: false,
+            hook_command: None,
+            hook_volume_threshold: None,
+            lock_on_start: false,
+            lock_pin: None,
+            custom_groups: BTreeMap::new(),
+            scenes: BTreeMap::new(),
+            label_properties: pactl::DEFAULT_LABEL_PROPERTIES.map(String::from).to_vec(),
+            keybindings: Vec::new(),
+            auto_mute: Vec::new(),
+            apps: BTreeMap::new(),
+            save_interval_secs: 2.0,
+            confirm_timeout_secs: 10.0,
+            min_command_interval_ms: 0,
+            load_error: None,
+            dirty: false,
+            last_save: None,
+        }
+    }
+}
+
+impl Config {
+    /// Load the config, falling back to defaults when it is missing or broken.
+    pub fn load() -> Self {
+        let Some(path) = config_path() else {
+            return Self::default();
+        };
+        let Ok(text) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+
+        match parse(&text) {
+            Ok((config, from_version)) => {
+                if from_version < CURRENT_VERSION {
+                    backup(&path, from_version);
+                    config.save();
+                }
+                config
+            }
+            Err(e) => {
+                eprintln!("Not using config {}: {}", path.display(), e);
+                Self {
+                    load_error: Some(e),
+                    ..Self::default()
+                }
+            }
+        }
+    }
+
+    /// Note a change to be written by the next due `save_if_due` (or `flush`).
+    pub fn save_later(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Write pending changes once `save_interval_secs` has passed since the
+    /// last write.
+    pub fn save_if_due(&mut self) {
+        if self.save_due_in() == Some(Duration::ZERO) {
+            self.flush();
+        }
+    }
+
+    /// How long until pending changes are due to be written; `None` when
+    /// there are none.
+    pub fn save_due_in(&self) -> Option<Duration> {
+        if !self.dirty {
+            return None;
+        }
+        let interval = Duration::from_secs_f32(self.save_interval_secs.clamp(0.0, 3600.0));
+        Some(self.last_save.map_or(Duration::ZERO, |t| interval.saturating_sub(t.elapsed())))
+    }
+
+    /// Write pending changes now, e.g. on exit.
+    pub fn flush(&mut self) {
+        if self.dirty {
+            self.dirty = false;
+            self.last_save = Some(Instant::now());
+            self.save();
+        }
+    }
+
+    pub fn save(&self) {
+        if self.load_error.is_some() {
+            return;
+        }
+        let Some(path) = config_path() else {
+            return;
+        };
+        let text = match toml::to_string_pretty(self) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("Failed to serialize config: {}", e);
+                return;
+            }
+        };
+        if let Some(dir) = path.parent()
+            && let Err(e) = fs::create_dir_all(dir)
+        {
+            eprintln!("Failed to create {}: {}", dir.display(), e);
+            return;
+        }
+        if let Err(e) = fs::write(&path, text) {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+        }
+    }
+
+    /// The name to show for a sink: the user's friendly name if set, otherwise
+    /// the description the server reports.
+    pub fn sink_label<'a>(&'a self, name: &str, description: &'a str) -> &'a str {
+        self.sink_names.get(name).map(String::as_str).unwrap_or(description)
+    }
+
+    pub fn app_volume(&self, app: &str) -> Option<f32> {
+        self.apps.get(app)?.volume
+    }
+
+    pub fn set_app_volume(&mut self, app: &str, volume: Option<f32>) {
+        self.app_mut(app).volume = volume;
+        self.prune_app(app);
+    }
+
+    /// The volume `app` should have on sink `device`: the device's own saved
+    /// volume if there is one, otherwise the app's general one.
+    pub fn app_volume_on(&self, app: &str, device: Option<&str>) -> Option<f32> {
+        device.and_then(|d| self.device_volume(app, d)).or_else(|| self.app_volume(app))
+    }
+
+    pub fn device_volume(&self, app: &str, device: &str) -> Option<f32> {
+        self.apps.get(app)?.devices.get(device).copied()
+    }
+
+    pub fn set_device_volume(&mut self, app: &str, device: &str, volume: Option<f32>) {
+        let devices = &mut self.app_mut(app).devices;
+        match volume {
+            Some(volume) => devices.insert(device.to_string(), volume),
+            None => devices.remove(device),
+        };
+        self.prune_app(app);
+    }
+
+    pub fn app_color(&self, app: &str) -> Option<[u8; 3]> {
+        self.apps.get(app)?.color
+    }
+
+    pub fn set_app_color(&mut self, app: &str, color: Option<[u8; 3]>) {
+        self.app_mut(app).color = color;
+        self.prune_app(app);
+    }
+
+    pub fn follows_system_mute(&self, app: &str) -> bool {
+        self.apps.get(app).is_some_and(|a| a.follow_system_mute)
+    }
+
+    pub fn set_follow_system_mute(&mut self, app: &str, follow: bool) {
+        self.app_mut(app).follow_system_mute = follow;
+        self.prune_app(app);
+    }
+
+    fn app_mut(&mut self, app: &str) -> &mut AppConfig {
+        self.apps.entry(app.to_string()).or_default()
+    }
+
+    /// Drop `app`'s table once nothing is left in it.
+    fn prune_app(&mut self, app: &str) {
+        if self.apps.get(app).is_some_and(AppConfig::is_empty) {
+            self.apps.remove(app);
+        }
+    }
+}
+
+/// Parse config text, migrating it to `CURRENT_VERSION`. Returns the config
+/// and the version the text was written in.
+fn parse(text: &str) -> Result<(Config, u32), String> {
+    let mut table: toml::Table = toml::from_str(text).map_err(|e| e.to_string())?;
+    let from_version = version_of(&table)?;
+    if from_version > CURRENT_VERSION {
+        return Err(format!(
+            "it was written by a newer version (schema {}, this build supports up to {}); \
+             update k_volume_mixer to use it",
+            from_version, CURRENT_VERSION
+        ));
+    }
+
+    migrate(&mut table, from_version);
+    let config = Config::deserialize(toml::Value::Table(table)).map_err(|e| e.to_string())?;
+    Ok((config, from_version))
+}
+
+/// Files from before versioning have no `version` key and count as v1.
+fn version_of(table: &toml::Table) -> Result<u32, String> {
+    match table.get("version") {
+        None => Ok(1),
+        Some(toml::Value::Integer(v)) => u32::try_from(*v).map_err(|_| format!("invalid version {}", v)),
+        Some(other) => Err(format!("invalid version {}", other)),
+    }
+}
+
+/// Upgrade `table` one schema step at a time from `from_version`.
+fn migrate(table: &mut toml::Table, from_version: u32) {
+    if from_version < 2 {
+        migrate_v1_to_v2(table);
+    }
+    table.insert("version".to_string(), toml::Value::Integer(CURRENT_VERSION.into()));
+}
+
+/// v1 kept remembered volumes in a flat `saved_volumes = { app = percent }`
+/// table; v2 moves them into per-app `[apps.<name>]` tables.
+fn migrate_v1_to_v2(table: &mut toml::Table) {
+    let Some(toml::Value::Table(saved)) = table.remove("saved_volumes") else {
+        return;
+    };
+
+    let apps = table
+        .entry("apps")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    let Some(apps) = apps.as_table_mut() else {
+        return;
+    };
+    for (app, volume) in saved {
+        if let Some(entry) = apps
+            .entry(app)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+        {
+            entry.insert("volume".to_string(), volume);
+        }
+    }
+}
+
+fn backup(path: &Path, version: u32) {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".v{}.bak", version));
+    if let Err(e) = fs::copy(path, &backup) {
+        eprintln!("Failed to back up config before migrating: {}", e);
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("k_volume_mixer").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1: &str = r#"
+suppress_remember_prompt = true
+
+[sink_names]
+"alsa_output.usb-dac" = "Desk DAC"
+
+[saved_volumes]
+Firefox = 40.0
+"Spotify" = 72.5
+"#;
+
+    #[test]
+    fn v1_migrates_to_v2() {
+        let (config, from_version) = parse(V1).unwrap();
+
+        assert_eq!(from_version, 1);
+        assert_eq!(config.version, CURRENT_VERSION);
+        assert!(config.suppress_remember_prompt);
+        assert_eq!(config.sink_names["alsa_output.usb-dac"], "Desk DAC");
+        assert_eq!(config.app_volume("Firefox"), Some(40.0));
+        assert_eq!(config.app_volume("Spotify"), Some(72.5));
+        assert_eq!(config.apps.len(), 2);
+    }
+
+    #[test]
+    fn migrated_config_round_trips() {
+        let (config, _) = parse(V1).unwrap();
+        let text = toml::to_string_pretty(&config).unwrap();
+
+        assert!(!text.contains("saved_volumes"));
+        let (reloaded, from_version) = parse(&text).unwrap();
+        assert_eq!(from_version, CURRENT_VERSION);
+        assert_eq!(reloaded, config);
+    }
+
+    #[test]
+    fn current_version_is_not_migrated() {
+        let text = "version = 2\nchannel_mode = \"max\"\n\n[apps.Firefox]\nvolume = 55.0\n";
+        let (config, from_version) = parse(text).unwrap();
+
+        assert_eq!(from_version, 2);
+        assert_eq!(config.app_volume("Firefox"), Some(55.0));
+        assert_eq!(config.channel_mode, pactl::ChannelMode::Max);
+    }
+
+    #[test]
+    fn newer_version_is_refused() {
+        let text = format!("version = {}\n", CURRENT_VERSION + 1);
+        let err = parse(&text).unwrap_err();
+
+        assert!(err.contains("newer version"), "{}", err);
+    }
+
+    #[test]
+    fn scenes_round_trip() {
+        let mut config = Config::default();
+        let mut scene = Scene {
+            default_sink: Some("alsa_output.usb-headset".to_string()),
+            ..Scene::default()
+        };
+        scene.cards.insert("alsa_card.usb-headset".to_string(), "output:analog-stereo".to_string());
+        scene.apps.insert(
+            "Discord".to_string(),
+            SceneApp {
+                sink: Some("alsa_output.usb-headset".to_string()),
+                volume: Some(80.0),
+                muted: false,
+            },
+        );
+        config.scenes.insert("Gaming".to_string(), scene);
+
+        let text = toml::to_string_pretty(&config).unwrap();
+        let (reloaded, _) = parse(&text).unwrap();
+        assert_eq!(reloaded, config);
+    }
+
+    #[test]
+    fn keybindings_are_read_as_tables() {
+        let text = "version = 2\n\n[[keybindings]]\nkey = \"Ctrl+M\"\naction = \"toggle-mute-app\"\napp = \"Firefox\"\n";
+        let (config, _) = parse(text).unwrap();
+
+        assert_eq!(config.keybindings.len(), 1);
+        assert_eq!(config.keybindings[0].key, "Ctrl+M");
+        assert_eq!(config.keybindings[0].app.as_deref(), Some("Firefox"));
+        let reloaded = parse(&toml::to_string_pretty(&config).unwrap()).unwrap().0;
+        assert_eq!(reloaded, config);
+    }
+
+    #[test]
+    fn device_volumes_override_the_app_volume() {
+        let mut config = Config::default();
+        config.set_app_volume("Firefox", Some(40.0));
+        config.set_device_volume("Firefox", "bluez_output.headset", Some(70.0));
+
+        assert_eq!(config.app_volume_on("Firefox", Some("bluez_output.headset")), Some(70.0));
+        assert_eq!(config.app_volume_on("Firefox", Some("alsa_output.speakers")), Some(40.0));
+        assert_eq!(config.app_volume_on("Firefox", None), Some(40.0));
+
+        let (reloaded, _) = parse(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(reloaded, config);
+
+        config.set_app_volume("Firefox", None);
+        config.set_device_volume("Firefox", "bluez_output.headset", None);
+        assert!(config.apps.is_empty());
+    }
+
+    #[test]
+    fn empty_app_tables_are_pruned() {
+        let mut config = Config::default();
+        config.set_app_volume("Firefox", Some(30.0));
+        config.set_app_volume("Firefox", None);
+
+        assert!(config.apps.is_empty());
+    }
+}
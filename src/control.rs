@@ -0,0 +1,311 @@
+//! Optional control socket for scripts: `$XDG_RUNTIME_DIR/k_volume_mixer.sock`.
+//!
+//! The protocol is one command per line; every command gets one reply line,
+//! `ok`, `ok <value>` or `error <message>` (`list` sends its rows first):
+//!
+//! ```text
+//! get-system                 -> ok 40.00
+//! set-system <percent>       -> ok
+//! mute-system | unmute-system | toggle-mute-system
+//! list                       -> <index>\t<volume|->\t<muted|unmuted>\t<name> per stream, then ok
+//! set-app <app> <percent>    -> ok 2      (number of streams changed)
+//! mute-app <app> | unmute-app <app>
+//! focus-app <app>            -> ok        (raises the window on that app's slider)
+//! ```
+//!
+//! `<app>` matches a stream's application name, binary or label, ignoring
+//! case, and may contain spaces. Try it with `socat - UNIX-CONNECT:<path>`.
+//!
+//! Commands go straight to the backend from the socket's threads, so they
+//! work while the window is hidden; the UI picks the changes up on its next
+//! refresh like any other external change. `focus-app` is the exception: it
+//! is handed to the UI, and is also what `k_volume_mixer focus <app>` sends.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+
+use eframe::egui;
+
+use crate::pactl::{self, ChannelMode, VolumeError};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Command {
+    GetSystem,
+    SetSystem(f32),
+    MuteSystem(Option<bool>), // None toggles
+    List,
+    SetApp(String, f32),
+    MuteApp(String, bool),
+    FocusApp(String),
+}
+
+pub struct Control {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    focus_rx: Receiver<String>, // apps asked for by `focus-app`
+}
+
+/// Where `focus-app` requests go: the UI, woken up to handle them.
+#[derive(Clone)]
+struct FocusSender {
+    tx: Sender<String>,
+    ctx: egui::Context,
+}
+
+impl Control {
+    /// Bind the socket and start serving it. Fails when the runtime dir is
+    /// missing or not private to this user, or another instance serves it.
+    pub fn start(ctx: &egui::Context) -> Result<Self, String> {
+        let path = socket_path()?;
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                return Err(format!("{} is already served by another instance", path.display()));
+            }
+            fs::remove_file(&path).map_err(|e| format!("removing stale {}: {}", path.display(), e))?;
+        }
+        let listener = UnixListener::bind(&path).map_err(|e| format!("binding {}: {}", path.display(), e))?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("securing {}: {}", path.display(), e))?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, focus_rx) = channel();
+        let focus = FocusSender { tx, ctx: ctx.clone() };
+        {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        let focus = focus.clone();
+                        thread::spawn(move || {
+                            let _ = serve(stream, &focus);
+                        });
+                    }
+                }
+            });
+        }
+
+        Ok(Self { path, stop, focus_rx })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Apps asked for by `focus-app` since the last call.
+    pub fn poll_focus(&self) -> Vec<String> {
+        self.focus_rx.try_iter().collect()
+    }
+
+    /// Stop accepting connections and remove the socket file.
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Wake the blocking accept so the listener thread sees the flag.
+        let _ = UnixStream::connect(&self.path);
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Send one command to a running mixer's socket and return its reply line.
+pub fn send(command: &str) -> Result<String, String> {
+    let path = socket_path()?;
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|e| format!("{}: {} (is the mixer running with its control socket on?)", path.display(), e))?;
+    writeln!(stream, "{}", command).map_err(|e| e.to_string())?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply).map_err(|e| e.to_string())?;
+    Ok(reply.trim_end().to_string())
+}
+
+fn socket_path() -> Result<PathBuf, String> {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .ok_or("XDG_RUNTIME_DIR is not set")?;
+    check_private(&dir)?;
+    Ok(dir.join("k_volume_mixer.sock"))
+}
+
+/// The socket is only as private as the directory it lives in: require the
+/// runtime dir to belong to us and be closed to everyone else.
+fn check_private(dir: &Path) -> Result<(), String> {
+    let meta = fs::metadata(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    // /proc/self is owned by the process's effective uid.
+    let uid = fs::metadata("/proc/self").map(|m| m.uid()).ok();
+    if uid.is_some_and(|uid| uid != meta.uid()) {
+        return Err(format!("{} belongs to another user", dir.display()));
+    }
+    if meta.mode() & 0o077 != 0 {
+        return Err(format!("{} is accessible to other users", dir.display()));
+    }
+    Ok(())
+}
+
+fn serve(stream: UnixStream, focus: &FocusSender) -> io::Result<()> {
+    let mut out = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match parse_command(&line) {
+            Ok(command) => run(command, &mut out, focus)?,
+            Err(e) => Err(e),
+        };
+        match reply {
+            Ok(Some(value)) => writeln!(out, "ok {}", value)?,
+            Ok(None) => writeln!(out, "ok")?,
+            Err(e) => writeln!(out, "error {}", e)?,
+        }
+    }
+    Ok(())
+}
+
+fn parse_command(line: &str) -> Result<Command, String> {
+    let line = line.trim();
+    let (verb, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+
+    let percent = |s: &str| -> Result<f32, String> {
+        let value: f32 = s.trim_end_matches('%').parse().map_err(|_| format!("not a percentage: {}", s))?;
+        if !(0.0..=150.0).contains(&value) {
+            return Err(format!("out of range (0-150): {}", s));
+        }
+        Ok(value)
+    };
+    let app = || -> Result<String, String> {
+        if rest.is_empty() {
+            return Err(format!("usage: {} <app>", verb));
+        }
+        Ok(rest.to_string())
+    };
+
+    match verb {
+        "get-system" => Ok(Command::GetSystem),
+        "set-system" => Ok(Command::SetSystem(percent(rest)?)),
+        "mute-system" => Ok(Command::MuteSystem(Some(true))),
+        "unmute-system" => Ok(Command::MuteSystem(Some(false))),
+        "toggle-mute-system" => Ok(Command::MuteSystem(None)),
+        "list" => Ok(Command::List),
+        "set-app" => {
+            let (name, value) = rest.rsplit_once(char::is_whitespace).ok_or("usage: set-app <app> <percent>")?;
+            Ok(Command::SetApp(name.trim().to_string(), percent(value)?))
+        }
+        "mute-app" => Ok(Command::MuteApp(app()?, true)),
+        "unmute-app" => Ok(Command::MuteApp(app()?, false)),
+        "focus-app" => Ok(Command::FocusApp(app()?)),
+        _ => Err(format!("unknown command: {}", verb)),
+    }
+}
+
+/// Carry out `command`, writing any extra rows to `out`. Returns the value
+/// for the `ok` line.
+fn run(command: Command, out: &mut impl Write, focus: &FocusSender) -> io::Result<Result<Option<String>, String>> {
+    let result = match command {
+        Command::GetSystem => pactl::get_system_state()
+            .map(|(vol, _)| Some(format!("{:.2}", vol)))
+            .map_err(|e| e.to_string()),
+        Command::SetSystem(vol) => pactl::set_main_volume(vol).map(|_| None).map_err(|e| e.to_string()),
+        Command::MuteSystem(Some(muted)) => pactl::set_main_mute(muted).map(|_| None).map_err(|e| e.to_string()),
+        Command::MuteSystem(None) => pactl::toggle_main_mute().map(|_| None).map_err(|e| e.to_string()),
+        Command::List => {
+            let streams = match pactl::parse_sink_inputs() {
+                Ok(streams) => streams,
+                Err(e) => return Ok(Err(e.to_string())),
+            };
+            let mut streams: Vec<_> = streams.into_iter().collect();
+            streams.sort_by_key(|(index, _)| *index);
+            for (index, props) in streams {
+                let volume = props
+                    .get("Volume")
+                    .and_then(|v| pactl::parse_volume_percent(v, ChannelMode::default()))
+                    .map_or_else(|| "-".to_string(), |v| format!("{:.2}", v));
+                let muted = if props.get("Mute").is_some_and(|m| m == "yes") { "muted" } else { "unmuted" };
+                let name = pactl::app_key(&props).unwrap_or("Unknown");
+                writeln!(out, "{}\t{}\t{}\t{}", index, volume, muted, name)?;
+            }
+            Ok(None)
+        }
+        Command::SetApp(app, vol) => for_app(&app, |index| pactl::set_app_volume(index, vol)),
+        Command::MuteApp(app, muted) => for_app(&app, |index| pactl::set_app_mute(index, muted)),
+        Command::FocusApp(app) => for_app(&app, |_| Ok(())).and_then(|_| {
+            focus.tx.send(app).map_err(|_| "the mixer is shutting down".to_string())?;
+            focus.ctx.request_repaint();
+            Ok(None)
+        }),
+    };
+    Ok(result)
+}
+
+/// Apply `f` to every stream of `app`, replying with how many there were.
+/// A number is taken as a process id. Stops at the first stream `f` fails on.
+fn for_app(app: &str, f: impl Fn(u32) -> Result<(), VolumeError>) -> Result<Option<String>, String> {
+    let streams = pactl::parse_sink_inputs().map_err(|e| e.to_string())?;
+    if let Ok(process_id) = app.parse::<u32>() {
+        let indices = pactl::sink_inputs_of_process(&streams, process_id);
+        if indices.is_empty() {
+            return Err(format!("no stream of process {}", process_id));
+        }
+        for index in &indices {
+            f(*index).map_err(|e| e.to_string())?;
+        }
+        return Ok(Some(indices.len().to_string()));
+    }
+    let app = app.to_lowercase();
+    let names = |props: &pactl::Props| {
+        [
+            props.get("application.name"),
+            props.get("application.process.binary"),
+            props.get("media.name"),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|name| name.to_lowercase() == app)
+    };
+
+    let mut count = 0;
+    for (index, props) in streams {
+        if names(&props) {
+            f(index).map_err(|e| e.to_string())?;
+            count += 1;
+        }
+    }
+    if count == 0 {
+        return Err(format!("no stream matches {}", app));
+    }
+    Ok(Some(count.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse_command("set-system 60"), Ok(Command::SetSystem(60.0)));
+        assert_eq!(parse_command("  mute-app firefox \n"), Ok(Command::MuteApp("firefox".to_string(), true)));
+        assert_eq!(
+            parse_command("set-app Google Chrome 35%"),
+            Ok(Command::SetApp("Google Chrome".to_string(), 35.0))
+        );
+        assert_eq!(parse_command("toggle-mute-system"), Ok(Command::MuteSystem(None)));
+        assert_eq!(parse_command("focus-app Discord"), Ok(Command::FocusApp("Discord".to_string())));
+    }
+
+    #[test]
+    fn rejects_bad_commands() {
+        assert!(parse_command("set-system loud").is_err());
+        assert!(parse_command("set-system 400").is_err());
+        assert!(parse_command("mute-app").is_err());
+        assert!(parse_command("set-app 40").is_err());
+        assert!(parse_command("reboot").is_err());
+    }
+}
@@ -0,0 +1,77 @@
+//! Panic hook: write the panic and a backtrace to a log file, tell the user
+//! where it is with whatever dialog tool is around, then exit.
+
+use std::backtrace::Backtrace;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::process::Command;
+
+pub fn install() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let log = write_log(info);
+        let message = match &log {
+            Some(path) => format!(
+                "K Volume Mixer crashed.\n\nDetails were written to:\n{}\n\nPlease attach this file when reporting the bug.",
+                path.display()
+            ),
+            None => "K Volume Mixer crashed. Run it from a terminal to see the details.".to_string(),
+        };
+        show_dialog(&message);
+
+        std::process::exit(101);
+    }));
+}
+
+fn write_log(info: &PanicHookInfo) -> Option<PathBuf> {
+    let path = log_path()?;
+    fs::create_dir_all(path.parent()?).ok()?;
+    let mut file = OpenOptions::new().create(true).append(true).open(&path).ok()?;
+
+    let thread = std::thread::current();
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    writeln!(
+        file,
+        "=== crash at unix time {} in thread '{}' (v{}) ===\n{}\n\n{}\n",
+        secs,
+        thread.name().unwrap_or("<unnamed>"),
+        env!("CARGO_PKG_VERSION"),
+        info,
+        Backtrace::force_capture()
+    )
+    .ok()?;
+    Some(path)
+}
+
+fn log_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))?;
+    Some(base.join("k_volume_mixer").join("crash.log"))
+}
+
+/// Best effort: the first dialog tool that runs wins.
+fn show_dialog(message: &str) {
+    let attempts: [(&str, &[&str]); 3] = [
+        ("zenity", &["--error", "--title=K Volume Mixer", "--text"]),
+        ("kdialog", &["--title", "K Volume Mixer", "--error"]),
+        ("notify-send", &["--urgency=critical", "K Volume Mixer"]),
+    ];
+    for (program, args) in attempts {
+        let shown = Command::new(program)
+            .args(args)
+            .arg(message)
+            .status()
+            .is_ok_and(|status| status.success());
+        if shown {
+            return;
+        }
+    }
+}
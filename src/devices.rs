@@ -0,0 +1,119 @@
+//! The device lists on their own: sinks, the default sink, `wpctl status`
+//! (for the sources) and the cards' profiles, re-read without touching the
+//! streams.
+//!
+//! The full poll rebuilds everything once a second; this is the cheap path
+//! for the dropdowns, run on demand and whenever `pactl subscribe` reports a
+//! sink, source, card or server change. Events come in bursts (a card
+//! switching profiles sends dozens), so requests arriving within `SETTLE` of
+//! each other share one read.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+use std::time::Duration;
+
+use eframe::egui;
+
+use crate::pactl::{self, Sink, VolumeError};
+
+const SETTLE: Duration = Duration::from_millis(150);
+const RESUBSCRIBE_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct Devices {
+    pub sinks: Vec<Sink>,
+    pub default_sink: Option<String>,
+    pub status: pactl::WpctlStatus,
+    pub cards: BTreeMap<String, String>, // card name -> active profile
+}
+
+impl Devices {
+    /// Read them all. A failure is left for the full poll to report.
+    pub fn read() -> Result<Self, VolumeError> {
+        let status = match pactl::wpctl_status() {
+            // See `BackendMode::NoWpctl`.
+            Err(VolumeError::BinaryNotFound(_)) => pactl::WpctlStatus::default(),
+            status => status?,
+        };
+        Ok(Self {
+            sinks: pactl::list_sinks()?,
+            default_sink: pactl::default_sink()?,
+            status,
+            cards: pactl::card_profiles()?,
+        })
+    }
+}
+
+pub struct DeviceWatch {
+    requests: Sender<()>,
+    updates: Receiver<Devices>,
+}
+
+impl DeviceWatch {
+    /// Start following device events; `ctx` is repainted after each read.
+    pub fn new(ctx: &egui::Context) -> Self {
+        let (requests, pending) = channel::<()>();
+        let (found, updates) = channel();
+        {
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                while pending.recv().is_ok() {
+                    thread::sleep(SETTLE);
+                    while pending.try_recv().is_ok() {}
+                    let Ok(devices) = Devices::read() else {
+                        continue;
+                    };
+                    if found.send(devices).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                }
+            });
+        }
+        {
+            let requests = requests.clone();
+            // `pactl subscribe` ends with the server; follow the next one.
+            // Stops without pactl, or once the app is gone.
+            thread::spawn(move || {
+                while let Ok(true) = follow_events(&requests) {
+                    thread::sleep(RESUBSCRIBE_INTERVAL);
+                }
+            });
+        }
+        Self { requests, updates }
+    }
+
+    /// Re-read the device lists soon.
+    pub fn refresh(&self) {
+        let _ = self.requests.send(());
+    }
+
+    /// The newest lists read since the last call.
+    pub fn latest(&self) -> Option<Devices> {
+        self.updates.try_iter().last()
+    }
+}
+
+/// Request a read for every device event until `pactl subscribe` exits.
+/// `Ok(false)` once nobody is listening any more.
+fn follow_events(requests: &Sender<()>) -> std::io::Result<bool> {
+    let mut child = Command::new("pactl")
+        .arg("subscribe")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let events = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    let mut listening = true;
+    for line in events.lines().map_while(Result::ok) {
+        let event = pactl::parse_subscribe_line(&line);
+        if event.as_ref().is_some_and(pactl::is_device_event) && requests.send(()).is_err() {
+            listening = false;
+            break;
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    Ok(listening)
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::pactl::{self, VolumeError};
+
+/// Presets offered in the per-app equalizer menu. Each preset gets its own
+/// `module-equalizer-sink` instance, so the curve tuned for it (e.g. with
+/// `qpaeq`) is remembered by the module under that sink name.
+pub const PRESETS: [&str; 4] = ["Flat", "Bass", "Voice", "Treble"];
+
+/// Directories PulseAudio installs its modules into on common distros.
+const MODULE_DIRS: [&str; 3] = ["/usr/lib", "/usr/lib64", "/usr/lib/x86_64-linux-gnu"];
+
+/// Routes applications through `module-equalizer-sink` instances, one per
+/// preset, and unloads whatever it loaded once nothing uses it any more.
+///
+/// The commands run in jobs for the command worker (`exec`), one job at a
+/// time. The UI only reads `routed`, which no job holds over a command.
+pub struct Equalizer {
+    pub available: bool,
+    modules: Arc<Mutex<HashMap<&'static str, u32>>>, // preset -> loaded module index
+    routed: Arc<Mutex<HashMap<u32, Routed>>>,        // sink-input index -> routing state
+}
+
+struct Routed {
+    preset: &'static str,
+    original_sink: String,
+}
+
+impl Equalizer {
+    pub fn new() -> Self {
+        Self {
+            available: module_installed(),
+            modules: Arc::new(Mutex::new(HashMap::new())),
+            routed: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The preset a sink-input is currently routed through, if any.
+    pub fn preset_for(&self, index: u32) -> Option<&'static str> {
+        lock(&self.routed).get(&index).map(|r| r.preset)
+    }
+
+    /// The job that routes `index` through `preset`'s equalizer sink, or back
+    /// to the sink it was on before when `preset` is `None`. `current_sink` is
+    /// the sink the stream is playing on right now. When the equalizer sink
+    /// can't be loaded, the stream goes back too and the loading error is
+    /// returned.
+    pub fn set_preset(
+        &self,
+        index: u32,
+        preset: Option<&'static str>,
+        current_sink: &str,
+    ) -> impl FnOnce() -> Result<(), VolumeError> + Send + 'static {
+        let modules = Arc::clone(&self.modules);
+        let routed = Arc::clone(&self.routed);
+        let current_sink = current_sink.to_string();
+        move || {
+            let mut modules = lock(&modules);
+            let original_sink = match lock(&routed).remove(&index) {
+                Some(r) => r.original_sink,
+                None => current_sink,
+            };
+
+            let result = match preset.map(|preset| (preset, ensure_loaded(&mut modules, preset))) {
+                Some((preset, Ok(()))) => pactl::move_sink_input(index, &sink_name(preset)).map(|()| {
+                    lock(&routed).insert(index, Routed { preset, original_sink });
+                }),
+                Some((_, Err(e))) => pactl::move_sink_input(index, &original_sink).and(Err(e)),
+                None => pactl::move_sink_input(index, &original_sink),
+            };
+
+            unload_unused(&mut modules, &routed);
+            result
+        }
+    }
+
+    /// Drop routing state for streams that no longer exist. Returns the job
+    /// that unloads the equalizer sinks they were keeping alive, if any.
+    pub fn retain_streams(&self, alive: impl Fn(u32) -> bool) -> Option<impl FnOnce() + Send + 'static> {
+        {
+            let mut routed = lock(&self.routed);
+            let before = routed.len();
+            routed.retain(|index, _| alive(*index));
+            if routed.len() == before {
+                return None;
+            }
+        }
+        let modules = Arc::clone(&self.modules);
+        let routed = Arc::clone(&self.routed);
+        Some(move || unload_unused(&mut lock(&modules), &routed))
+    }
+
+    /// The job that moves every routed stream back and unloads all equalizer
+    /// sinks. Failures are only logged; this runs on the way out.
+    pub fn cleanup(&self) -> impl FnOnce() + Send + 'static {
+        let modules = Arc::clone(&self.modules);
+        let routed = Arc::clone(&self.routed);
+        move || {
+            let streams: Vec<(u32, Routed)> = lock(&routed).drain().collect();
+            for (index, r) in streams {
+                let _ = pactl::move_sink_input(index, &r.original_sink);
+            }
+            unload_unused(&mut lock(&modules), &routed);
+        }
+    }
+}
+
+fn ensure_loaded(modules: &mut HashMap<&'static str, u32>, preset: &'static str) -> Result<(), VolumeError> {
+    if modules.contains_key(preset) {
+        return Ok(());
+    }
+
+    let master = pactl::default_sink()?.unwrap_or_else(|| "@DEFAULT_SINK@".to_string());
+    let args = [
+        format!("sink_name={}", sink_name(preset)),
+        format!("sink_master={}", master),
+        format!("sink_properties=device.description=\"Equalizer ({})\"", preset),
+    ];
+    let module = pactl::load_module("module-equalizer-sink", &args)?;
+    modules.insert(preset, module);
+    Ok(())
+}
+
+/// Unload the sinks no stream uses. One that fails to unload is kept, to be
+/// tried again next time.
+fn unload_unused(modules: &mut HashMap<&'static str, u32>, routed: &Mutex<HashMap<u32, Routed>>) {
+    let in_use: Vec<&str> = lock(routed).values().map(|r| r.preset).collect();
+    modules.retain(|preset, module| in_use.contains(preset) || pactl::unload_module(*module).is_err());
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn sink_name(preset: &str) -> String {
+    format!("{}eq_{}", pactl::OWN_SINK_PREFIX, preset.to_lowercase())
+}
+
+fn module_installed() -> bool {
+    MODULE_DIRS.iter().any(|dir| {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return false;
+        };
+        entries.flatten().any(|entry| {
+            let name = entry.file_name();
+            name.to_string_lossy().starts_with("pulse")
+                && entry.path().join("modules/module-equalizer-sink.so").exists()
+        })
+    })
+}
@@ -0,0 +1,313 @@
+//! Where the short-lived commands are started: the `pactl`/`wpctl`/
+//! `pw-metadata` reads and changes, the hook command and media player
+//! control.
+//!
+//! They all run one at a time on a single worker thread that owns the queue.
+//! The UI thread only posts jobs (`post`) and never waits for one; other
+//! threads may also call `run`/`read` and wait for the answer. A job posted
+//! with a key replaces a waiting job with the same key, so a slider drag on a
+//! slow machine sends only its latest value.
+//!
+//! Two settings apply to all of them:
+//! - dry-run mode (`--dry-run`): commands that change something are printed
+//!   instead of run; reads still run, so the real state is shown.
+//! - the minimum interval (`min_command_interval_ms`): the worker leaves at
+//!   least this long between two starts.
+//!
+//! A command that hasn't finished after `DEADLINE` is killed, so a hung
+//! sound server can't hang the worker too.
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::channel;
+use std::sync::{Condvar, Mutex, MutexGuard, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::pactl::VolumeError;
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+static MIN_INTERVAL_MS: AtomicU64 = AtomicU64::new(0);
+
+/// How long a command may take before it is killed.
+const DEADLINE: Duration = Duration::from_secs(5);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Jobs waiting for the worker, oldest first, and the key of the one it is
+/// running.
+struct Queue {
+    waiting: VecDeque<(Option<String>, Job)>,
+    running: Option<String>,
+}
+
+static QUEUE: Mutex<Queue> = Mutex::new(Queue { waiting: VecDeque::new(), running: None });
+static QUEUED: Condvar = Condvar::new();
+static WORKER: Once = Once::new();
+
+thread_local! {
+    static ON_WORKER: Cell<bool> = const { Cell::new(false) };
+    /// When the worker last started a command, for the minimum interval.
+    static LAST_START: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Commands actually started, so tests can check dry runs start none.
+#[cfg(test)]
+pub static STARTED: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// Turn dry-run mode on or off for every thread.
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+/// Leave at least `interval` between two command starts (zero: no limit).
+pub fn set_min_interval(interval: Duration) {
+    MIN_INTERVAL_MS.store(interval.as_millis() as u64, Ordering::Relaxed);
+}
+
+fn min_interval() -> Duration {
+    Duration::from_millis(MIN_INTERVAL_MS.load(Ordering::Relaxed))
+}
+
+fn queue() -> MutexGuard<'static, Queue> {
+    QUEUE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Hand `job` to the worker and return at once. With a `key`, a waiting job
+/// posted with the same key is replaced, keeping its place in the queue.
+pub fn post(key: Option<String>, job: impl FnOnce() + Send + 'static) {
+    WORKER.call_once(|| {
+        thread::Builder::new()
+            .name("commands".to_string())
+            .spawn(work)
+            .expect("failed to start the command worker");
+    });
+    let job: Job = Box::new(job);
+    let mut queue = queue();
+    match key.as_ref().and_then(|key| queue.waiting.iter_mut().find(|(k, _)| k.as_ref() == Some(key))) {
+        Some((_, waiting)) => *waiting = job,
+        None => queue.waiting.push_back((key, job)),
+    }
+    QUEUED.notify_one();
+}
+
+/// Whether a job posted with `key` is still waiting or running.
+pub fn pending(key: &str) -> bool {
+    let queue = queue();
+    queue.running.as_deref() == Some(key) || queue.waiting.iter().any(|(k, _)| k.as_deref() == Some(key))
+}
+
+/// Wait until every job posted so far has run, but no longer than `timeout`.
+/// For the way out, when there is nothing else left to do.
+pub fn flush(timeout: Duration) {
+    let (done, finished) = channel();
+    post(None, move || {
+        let _ = done.send(());
+    });
+    let _ = finished.recv_timeout(timeout);
+}
+
+/// The worker: run the jobs in order, for as long as the app runs.
+fn work() {
+    ON_WORKER.set(true);
+    loop {
+        let job = {
+            let mut queue = queue();
+            loop {
+                if let Some((key, job)) = queue.waiting.pop_front() {
+                    queue.running = key;
+                    break job;
+                }
+                queue = QUEUED.wait(queue).unwrap_or_else(|e| e.into_inner());
+            }
+        };
+        job();
+        queue().running = None;
+    }
+}
+
+/// Run `f` on the worker and wait for its answer; on the worker itself, run
+/// it right away.
+fn on_worker<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    if ON_WORKER.get() {
+        return f();
+    }
+    let (answer, answered) = channel();
+    post(None, move || {
+        let _ = answer.send(f());
+    });
+    // A panicking job ends the whole app (see `crash`), so there is always one.
+    answered.recv().expect("the command worker stopped")
+}
+
+/// Wait until the minimum interval since the last start is up. Only the
+/// worker starts commands, so only the worker ever waits here.
+fn take_turn() {
+    if let Some(previous) = LAST_START.get() {
+        let wait = min_interval().saturating_sub(previous.elapsed());
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+    LAST_START.set(Some(Instant::now()));
+}
+
+/// Run a command that changes something and wait for it, or in dry-run mode
+/// only print it.
+pub fn run(program: &str, args: &[&str]) -> Result<Output, VolumeError> {
+    if DRY_RUN.load(Ordering::Relaxed) {
+        eprintln!("dry run: {} {}", program, args.join(" "));
+        return Err(VolumeError::DryRun);
+    }
+    read(program, args)
+}
+
+/// Run a command that only reads, dry run or not, and wait for it.
+pub fn read(program: &str, args: &[&str]) -> Result<Output, VolumeError> {
+    let mut command = Command::new(program);
+    command.args(args);
+    on_worker(move || {
+        take_turn();
+        #[cfg(test)]
+        STARTED.fetch_add(1, Ordering::Relaxed);
+        output_within(&mut command, DEADLINE)
+    })
+}
+
+/// Start `command` on the worker without waiting for it to finish, or in
+/// dry-run mode only print it. For commands with side effects of their own,
+/// like the hook command; one that fails to start is logged.
+pub fn spawn(mut command: Command) -> Result<(), VolumeError> {
+    if DRY_RUN.load(Ordering::Relaxed) {
+        eprintln!("dry run: {:?}", command);
+        return Err(VolumeError::DryRun);
+    }
+    post(None, move || {
+        take_turn();
+        #[cfg(test)]
+        STARTED.fetch_add(1, Ordering::Relaxed);
+        match command.spawn() {
+            // Reaped on its own thread, however long it runs.
+            Ok(mut child) => {
+                thread::spawn(move || child.wait());
+            }
+            Err(e) => eprintln!("{:?}: {}", command, start_error(&command.get_program().to_string_lossy(), e)),
+        }
+    });
+    Ok(())
+}
+
+/// Run `command` to the end and collect its output, unless it takes longer
+/// than `deadline`: then it is killed and the answer is `Timeout`.
+fn output_within(command: &mut Command, deadline: Duration) -> Result<Output, VolumeError> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| start_error(&program, e))?;
+    // Drained on their own threads so a chatty command can't fill a pipe
+    // and stall before it exits.
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    let start = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if start.elapsed() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(VolumeError::Timeout);
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(2)),
+            Err(e) => return Err(start_error(&program, e)),
+        }
+    };
+    let output = Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    };
+    check(&program, Ok(output))
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    })
+}
+
+/// Sort the outcome of running `program` into success or a `VolumeError`.
+pub fn check(program: &str, output: io::Result<Output>) -> Result<Output, VolumeError> {
+    let output = output.map_err(|e| start_error(program, e))?;
+    if !output.status.success() {
+        return Err(VolumeError::CommandFailed {
+            code: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+    Ok(output)
+}
+
+/// Why `program` couldn't be run at all.
+fn start_error(program: &str, e: io::Error) -> VolumeError {
+    match e.kind() {
+        io::ErrorKind::NotFound => VolumeError::BinaryNotFound(program.to_string()),
+        _ => VolumeError::CommandFailed { code: None, stderr: e.to_string() },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_wait_for_the_minimum_interval() {
+        set_min_interval(Duration::from_millis(50));
+        let start = Instant::now();
+        read("true", &[]).unwrap();
+        read("true", &[]).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        set_min_interval(Duration::ZERO);
+    }
+
+    #[test]
+    fn keyed_jobs_replace_waiting_ones() {
+        // Hold the worker so the keyed jobs have to wait.
+        let (release, held) = channel::<()>();
+        post(None, move || {
+            let _ = held.recv();
+        });
+        let (sent, received) = channel();
+        for value in [1, 2] {
+            let sent = sent.clone();
+            post(Some("test:volume".to_string()), move || {
+                let _ = sent.send(value);
+            });
+        }
+        drop(sent);
+        assert!(pending("test:volume"));
+        release.send(()).unwrap();
+        assert_eq!(received.iter().collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn commands_past_the_deadline_are_killed() {
+        let start = Instant::now();
+        let hung = output_within(Command::new("sleep").arg("5"), Duration::from_millis(100));
+        assert_eq!(hung.unwrap_err(), VolumeError::Timeout);
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        let quick = output_within(Command::new("echo").arg("ok"), Duration::from_secs(5)).unwrap();
+        assert_eq!(quick.stdout, b"ok\n");
+    }
+}
@@ -0,0 +1,208 @@
+//! The config's `hook_command`: a shell command run on volume events, for
+//! automations the mixer doesn't do itself.
+//!
+//! ```toml
+//! hook_command = "notify-send \"$K_VOLUME_MIXER_EVENT\" \"$K_VOLUME_MIXER_NAME $K_VOLUME_MIXER_VALUE\""
+//! hook_volume_threshold = 100.0
+//! ```
+//!
+//! Events are `stream-new`, `mute` (value `true`/`false`), and with a
+//! threshold set `volume-above`/`volume-below` when a volume crosses it. The
+//! command runs under `sh -c` with `K_VOLUME_MIXER_EVENT`, `_TARGET`
+//! (`system` or `sink-input:<index>`, as in `monitor`), `_NAME` and `_VALUE`
+//! set, and the same four as `$1`..`$4`. At most `MAX_RUNS` start per
+//! `RATE_WINDOW`; events beyond that are dropped and counted.
+
+use std::collections::{HashMap, VecDeque};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::exec;
+
+const MAX_RUNS: usize = 5;
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HookEvent {
+    pub event: &'static str,
+    pub target: String,
+    pub name: String,
+    pub value: String,
+}
+
+/// What the hooks compare between two polls.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Levels {
+    pub system: Option<(f32, bool)>,
+    pub streams: HashMap<u32, StreamLevel>, // sink-input index -> state
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamLevel {
+    pub name: String,
+    pub volume: Option<f32>,
+    pub muted: bool,
+}
+
+#[derive(Default)]
+pub struct Hooks {
+    last: Option<Levels>,
+    runs: VecDeque<Instant>,
+    dropped: usize,
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `command` for whatever changed since the last call. The first call
+    /// only records the starting state.
+    pub fn observe(&mut self, command: &str, threshold: Option<f32>, levels: Levels) {
+        let events = match &self.last {
+            Some(last) => diff(last, &levels, threshold),
+            None => Vec::new(),
+        };
+        self.last = Some(levels);
+        for event in events {
+            self.run(command, event);
+        }
+    }
+
+    /// Forget the recorded state, e.g. when the command is turned off.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// Events dropped by the rate limit so far.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    fn run(&mut self, command: &str, event: HookEvent) {
+        let now = Instant::now();
+        while self.runs.front().is_some_and(|t| now.duration_since(*t) >= RATE_WINDOW) {
+            self.runs.pop_front();
+        }
+        if self.runs.len() >= MAX_RUNS {
+            self.dropped += 1;
+            return;
+        }
+        self.runs.push_back(now);
+
+        let mut hook = Command::new("sh");
+        hook.arg("-c")
+            .arg(command)
+            .arg("k_volume_mixer") // $0
+            .args([event.event, &event.target, &event.name, &event.value])
+            .env("K_VOLUME_MIXER_EVENT", event.event)
+            .env("K_VOLUME_MIXER_TARGET", &event.target)
+            .env("K_VOLUME_MIXER_NAME", &event.name)
+            .env("K_VOLUME_MIXER_VALUE", &event.value)
+            .stdin(Stdio::null());
+        // Started by the command worker, off the UI thread; a dry run has
+        // already printed it.
+        let _ = exec::spawn(hook);
+    }
+}
+
+/// Events between two polls, in a stable order (system first, then streams
+/// by index).
+fn diff(prev: &Levels, next: &Levels, threshold: Option<f32>) -> Vec<HookEvent> {
+    let mut events = Vec::new();
+    let event = |event, target: &str, name: &str, value: String| HookEvent {
+        event,
+        target: target.to_string(),
+        name: name.to_string(),
+        value,
+    };
+
+    if let (Some((old_vol, old_muted)), Some((vol, muted))) = (prev.system, next.system) {
+        if let Some(crossing) = crossing(Some(old_vol), Some(vol), threshold) {
+            events.push(event(crossing, "system", "System", format!("{:.2}", vol)));
+        }
+        if old_muted != muted {
+            events.push(event("mute", "system", "System", muted.to_string()));
+        }
+    }
+
+    let mut indices: Vec<&u32> = next.streams.keys().collect();
+    indices.sort();
+    for index in indices {
+        let stream = &next.streams[index];
+        let target = format!("sink-input:{}", index);
+        let volume = stream.volume.map_or_else(String::new, |v| format!("{:.2}", v));
+        match prev.streams.get(index) {
+            None => events.push(event("stream-new", &target, &stream.name, volume)),
+            Some(old) => {
+                if let Some(crossing) = crossing(old.volume, stream.volume, threshold) {
+                    events.push(event(crossing, &target, &stream.name, volume));
+                }
+                if old.muted != stream.muted {
+                    events.push(event("mute", &target, &stream.name, stream.muted.to_string()));
+                }
+            }
+        }
+    }
+    events
+}
+
+fn crossing(old: Option<f32>, new: Option<f32>, threshold: Option<f32>) -> Option<&'static str> {
+    let (old, new, threshold) = (old?, new?, threshold?);
+    match (old > threshold, new > threshold) {
+        (false, true) => Some("volume-above"),
+        (true, false) => Some("volume-below"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(name: &str, volume: f32, muted: bool) -> StreamLevel {
+        StreamLevel {
+            name: name.to_string(),
+            volume: Some(volume),
+            muted,
+        }
+    }
+
+    #[test]
+    fn reports_new_streams_mutes_and_crossings() {
+        let prev = Levels {
+            system: Some((90.0, false)),
+            streams: HashMap::from([(3, stream("Firefox", 50.0, false))]),
+        };
+        let next = Levels {
+            system: Some((110.0, false)),
+            streams: HashMap::from([(3, stream("Firefox", 55.0, true)), (7, stream("mpv", 80.0, false))]),
+        };
+
+        let events: Vec<(&str, String)> =
+            diff(&prev, &next, Some(100.0)).into_iter().map(|e| (e.event, e.target)).collect();
+        assert_eq!(
+            events,
+            [
+                ("volume-above", "system".to_string()),
+                ("mute", "sink-input:3".to_string()),
+                ("stream-new", "sink-input:7".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_threshold_means_no_volume_events() {
+        let prev = Levels {
+            system: Some((10.0, false)),
+            streams: HashMap::new(),
+        };
+        let next = Levels {
+            system: Some((150.0, false)),
+            streams: HashMap::new(),
+        };
+
+        assert!(diff(&prev, &next, None).is_empty());
+        assert_eq!(diff(&next, &prev, Some(100.0))[0].event, "volume-below");
+    }
+}
@@ -0,0 +1,218 @@
+//! System-wide key grabs via `global-hotkey` (X11).
+//!
+//! egui doesn't report the XF86Audio keys at all, so even the "window is
+//! focused" case has to grab them. While the window has focus, presses are
+//! queued for `update` to handle like any other input; otherwise (only when
+//! the global option keeps the grab alive) they are applied straight away from
+//! the hotkey thread, since an unfocused or minimized window may not repaint.
+//!
+//! The optional panic key is grabbed all the time and always handled on the
+//! hotkey thread: it mutes the system and every stream, and a second press
+//! puts each back as it was.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, channel};
+use std::sync::{Arc, Mutex};
+
+use eframe::egui;
+use global_hotkey::hotkey::{Code, HotKey};
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+
+use crate::pactl;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HotkeyAction {
+    RaiseVolume,
+    LowerVolume,
+    ToggleMute,
+    Panic,
+}
+
+/// Mute states from before the panic key was pressed.
+struct PanicState {
+    system_muted: bool,
+    streams: HashMap<u32, bool>, // sink-input index -> muted
+}
+
+pub struct Hotkeys {
+    manager: Option<GlobalHotKeyManager>,
+    keys: Vec<HotKey>,
+    registered: bool,
+    panic_key: Option<HotKey>,
+    bindings: Arc<Mutex<HashMap<u32, HotkeyAction>>>, // hotkey id -> action
+    panicked: Arc<Mutex<Option<PanicState>>>,
+    window_focused: Arc<AtomicBool>,
+    rx: Receiver<HotkeyAction>,
+}
+
+impl Hotkeys {
+    pub fn new(ctx: &egui::Context) -> Self {
+        let manager = GlobalHotKeyManager::new()
+            .map_err(|e| eprintln!("Global hotkeys unavailable: {}", e))
+            .ok();
+
+        let media_keys = [
+            (Code::AudioVolumeUp, HotkeyAction::RaiseVolume),
+            (Code::AudioVolumeDown, HotkeyAction::LowerVolume),
+            (Code::AudioVolumeMute, HotkeyAction::ToggleMute),
+        ];
+        let keys: Vec<HotKey> = media_keys
+            .iter()
+            .map(|(code, _)| HotKey::new(None, *code))
+            .collect();
+        let bindings: HashMap<u32, HotkeyAction> = keys
+            .iter()
+            .zip(media_keys)
+            .map(|(key, (_, action))| (key.id(), action))
+            .collect();
+        let bindings = Arc::new(Mutex::new(bindings));
+        let panicked = Arc::new(Mutex::new(None));
+
+        let window_focused = Arc::new(AtomicBool::new(true));
+        let (tx, rx) = channel();
+        {
+            let bindings = Arc::clone(&bindings);
+            let panicked = Arc::clone(&panicked);
+            let window_focused = Arc::clone(&window_focused);
+            let ctx = ctx.clone();
+            GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+                if event.state != HotKeyState::Pressed {
+                    return;
+                }
+                let Some(action) = bindings.lock().ok().and_then(|b| b.get(&event.id).copied()) else {
+                    return;
+                };
+                if action == HotkeyAction::Panic {
+                    toggle_panic(&panicked);
+                } else if window_focused.load(Ordering::Relaxed) {
+                    let _ = tx.send(action);
+                } else {
+                    apply_in_background(action);
+                }
+                ctx.request_repaint();
+            }));
+        }
+
+        Self {
+            manager,
+            keys,
+            registered: false,
+            panic_key: None,
+            bindings,
+            panicked,
+            window_focused,
+            rx,
+        }
+    }
+
+    pub fn available(&self) -> bool {
+        self.manager.is_some()
+    }
+
+    /// Grab the keys while `active`, release them otherwise.
+    pub fn set_active(&mut self, active: bool) {
+        if active == self.registered {
+            return;
+        }
+        let Some(manager) = &self.manager else {
+            return;
+        };
+        let result = if active {
+            manager.register_all(&self.keys)
+        } else {
+            manager.unregister_all(&self.keys)
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to update global hotkeys: {}", e);
+        }
+        self.registered = active;
+    }
+
+    /// Grab `combo` (e.g. `Ctrl+Alt+M`) as the panic key, replacing the
+    /// previous one; `None` releases it.
+    pub fn set_panic_key(&mut self, combo: Option<&str>) -> Result<(), String> {
+        if let Some(old) = self.panic_key.take()
+            && let Some(manager) = &self.manager
+        {
+            let _ = manager.unregister(old);
+            if let Ok(mut bindings) = self.bindings.lock() {
+                bindings.remove(&old.id());
+            }
+        }
+        let Some(combo) = combo else {
+            return Ok(());
+        };
+        let Some(manager) = &self.manager else {
+            return Err("global hotkeys aren't available".to_string());
+        };
+        let key: HotKey = combo.parse().map_err(|e| format!("{}", e))?;
+        if self.keys.contains(&key) {
+            return Err("that's a volume media key".to_string());
+        }
+        manager.register(key).map_err(|e| format!("{}", e))?;
+        if let Ok(mut bindings) = self.bindings.lock() {
+            bindings.insert(key.id(), HotkeyAction::Panic);
+        }
+        self.panic_key = Some(key);
+        Ok(())
+    }
+
+    /// Whether the panic key has muted everything and not been pressed again.
+    pub fn panicked(&self) -> bool {
+        self.panicked.lock().is_ok_and(|p| p.is_some())
+    }
+
+    pub fn set_window_focused(&self, focused: bool) {
+        self.window_focused.store(focused, Ordering::Relaxed);
+    }
+
+    /// Presses received while the window was focused.
+    pub fn poll(&self) -> Vec<HotkeyAction> {
+        self.rx.try_iter().collect()
+    }
+}
+
+/// Handle a press without the UI: relative changes straight to the default sink.
+/// With no window to show it in, a failure is only logged (by `pactl`).
+fn apply_in_background(action: HotkeyAction) {
+    let _ = match action {
+        HotkeyAction::RaiseVolume => pactl::step_main_volume(crate::VOLUME_STEP),
+        HotkeyAction::LowerVolume => pactl::step_main_volume(-crate::VOLUME_STEP),
+        HotkeyAction::ToggleMute => pactl::toggle_main_mute(),
+        HotkeyAction::Panic => Ok(()),
+    };
+}
+
+/// Mute the system and every stream, remembering how they were; or, when
+/// already panicked, put them back. Failed changes are logged and skipped, so
+/// one stream can't keep the rest unmuted.
+fn toggle_panic(panicked: &Mutex<Option<PanicState>>) {
+    let Ok(mut panicked) = panicked.lock() else {
+        return;
+    };
+    match panicked.take() {
+        Some(prior) => {
+            let _ = pactl::set_main_mute(prior.system_muted);
+            // Streams that have gone since are simply not found.
+            for (index, muted) in prior.streams {
+                let _ = pactl::set_app_mute(index, muted);
+            }
+        }
+        None => {
+            let system_muted = pactl::get_system_state().is_ok_and(|(_, muted)| muted);
+            // Without the streams, at least the system is muted.
+            let streams: HashMap<u32, bool> = pactl::parse_sink_inputs()
+                .inspect_err(|e| eprintln!("panic: streams not muted: {}", e))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(index, props)| (index, props.get("Mute").is_some_and(|m| m == "yes")))
+                .collect();
+            let _ = pactl::set_main_mute(true);
+            for index in streams.keys() {
+                let _ = pactl::set_app_mute(*index, true);
+            }
+            *panicked = Some(PanicState { system_muted, streams });
+        }
+    }
+}
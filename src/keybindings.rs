@@ -0,0 +1,169 @@
+//! User-defined shortcuts from the config's `[[keybindings]]` tables:
+//!
+//! ```toml
+//! [[keybindings]]
+//! key = "Ctrl+M"
+//! action = "toggle-mute-app"
+//! app = "Firefox"
+//! ```
+//!
+//! `key` is modifiers (`Ctrl`, `Shift`, `Alt`) and one key name joined by `+`.
+//! Actions: `toggle-mute-system`, `raise-system`, `lower-system`, and with an
+//! `app` (matched against its application name or binary, ignoring case)
+//! `toggle-mute-app`, `raise-app`, `lower-app`.
+
+use eframe::egui::{Key, KeyboardShortcut, Modifiers};
+
+use crate::config::KeybindingConfig;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BindingAction {
+    ToggleMuteSystem,
+    RaiseSystem,
+    LowerSystem,
+    ToggleMuteApp(String),
+    RaiseApp(String),
+    LowerApp(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Binding {
+    pub shortcut: KeyboardShortcut,
+    pub action: BindingAction,
+}
+
+/// Keys the mixer already handles itself (see `handle_keys`).
+const BUILT_IN: [(Modifiers, Key); 6] = [
+    (Modifiers::NONE, Key::Tab),
+    (Modifiers::SHIFT, Key::Tab),
+    (Modifiers::NONE, Key::Escape),
+    (Modifiers::NONE, Key::ArrowUp),
+    (Modifiers::NONE, Key::ArrowDown),
+    (Modifiers::NONE, Key::F2),
+];
+
+/// Parse the configured bindings. Invalid or conflicting entries are left
+/// out and described in the returned errors, one per entry.
+pub fn parse(configs: &[KeybindingConfig]) -> (Vec<Binding>, Vec<String>) {
+    let mut bindings: Vec<Binding> = Vec::new();
+    let mut errors = Vec::new();
+
+    for (i, config) in configs.iter().enumerate() {
+        let parsed = parse_shortcut(&config.key).and_then(|shortcut| {
+            let action = parse_action(&config.action, config.app.as_deref())?;
+            Ok(Binding { shortcut, action })
+        });
+        let binding = match parsed {
+            Ok(binding) => binding,
+            Err(e) => {
+                errors.push(format!("keybinding {} ({}): {}", i + 1, config.key, e));
+                continue;
+            }
+        };
+
+        let shortcut = binding.shortcut;
+        if BUILT_IN.contains(&(shortcut.modifiers, shortcut.logical_key)) {
+            errors.push(format!("keybinding {} ({}): already used by the mixer", i + 1, config.key));
+        } else if bindings.iter().any(|b| b.shortcut == shortcut) {
+            errors.push(format!("keybinding {} ({}): bound more than once", i + 1, config.key));
+        } else {
+            bindings.push(binding);
+        }
+    }
+
+    // egui matches modifiers loosely (Shift+M also triggers M), so try the
+    // more specific shortcuts first.
+    bindings.sort_by_key(|b| std::cmp::Reverse(modifier_count(b.shortcut.modifiers)));
+    (bindings, errors)
+}
+
+fn parse_shortcut(text: &str) -> Result<KeyboardShortcut, String> {
+    let mut modifiers = Modifiers::NONE;
+    let mut key = None;
+    for part in text.split('+').map(str::trim) {
+        match part.to_lowercase().as_str() {
+            "ctrl" | "control" => modifiers = modifiers | Modifiers::CTRL,
+            "shift" => modifiers = modifiers | Modifiers::SHIFT,
+            "alt" => modifiers = modifiers | Modifiers::ALT,
+            _ if key.is_some() => return Err(format!("more than one key in \"{}\"", text)),
+            _ => key = Some(parse_key(part).ok_or_else(|| format!("unknown key \"{}\"", part))?),
+        }
+    }
+    let key = key.ok_or_else(|| format!("no key in \"{}\"", text))?;
+    Ok(KeyboardShortcut::new(modifiers, key))
+}
+
+/// egui's key names (`M`, `F5`, `Up`, `PageDown`, ...), ignoring case.
+fn parse_key(name: &str) -> Option<Key> {
+    Key::from_name(name).or_else(|| Key::ALL.iter().copied().find(|key| key.name().eq_ignore_ascii_case(name)))
+}
+
+fn parse_action(action: &str, app: Option<&str>) -> Result<BindingAction, String> {
+    let app = || {
+        app.filter(|a| !a.trim().is_empty())
+            .map(str::to_string)
+            .ok_or_else(|| format!("\"{}\" needs an app", action))
+    };
+    match action {
+        "toggle-mute-system" => Ok(BindingAction::ToggleMuteSystem),
+        "raise-system" => Ok(BindingAction::RaiseSystem),
+        "lower-system" => Ok(BindingAction::LowerSystem),
+        "toggle-mute-app" => Ok(BindingAction::ToggleMuteApp(app()?)),
+        "raise-app" => Ok(BindingAction::RaiseApp(app()?)),
+        "lower-app" => Ok(BindingAction::LowerApp(app()?)),
+        _ => Err(format!("unknown action \"{}\"", action)),
+    }
+}
+
+fn modifier_count(modifiers: Modifiers) -> usize {
+    [modifiers.ctrl, modifiers.shift, modifiers.alt].iter().filter(|m| **m).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(key: &str, action: &str, app: Option<&str>) -> KeybindingConfig {
+        KeybindingConfig {
+            key: key.to_string(),
+            action: action.to_string(),
+            app: app.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn parses_shortcuts_and_actions() {
+        let (bindings, errors) = parse(&[
+            config("Ctrl+M", "toggle-mute-app", Some("Firefox")),
+            config("ctrl + shift + up", "raise-system", None),
+        ]);
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(bindings.len(), 2);
+        // Most modifiers first.
+        assert_eq!(bindings[0].shortcut, KeyboardShortcut::new(Modifiers::CTRL | Modifiers::SHIFT, Key::ArrowUp));
+        assert_eq!(bindings[0].action, BindingAction::RaiseSystem);
+        assert_eq!(bindings[1].shortcut, KeyboardShortcut::new(Modifiers::CTRL, Key::M));
+        assert_eq!(bindings[1].action, BindingAction::ToggleMuteApp("Firefox".to_string()));
+    }
+
+    #[test]
+    fn reports_conflicts_and_mistakes() {
+        let (bindings, errors) = parse(&[
+            config("Ctrl+M", "toggle-mute-system", None),
+            config("ctrl+m", "raise-system", None),
+            config("Tab", "lower-system", None),
+            config("Ctrl+Banana", "raise-system", None),
+            config("Ctrl+K", "raise-app", None),
+            config("Ctrl+J", "dance", None),
+        ]);
+
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(errors.len(), 5, "{:?}", errors);
+        assert!(errors[0].contains("more than once"));
+        assert!(errors[1].contains("already used"));
+        assert!(errors[2].contains("unknown key"));
+        assert!(errors[3].contains("needs an app"));
+        assert!(errors[4].contains("unknown action"));
+    }
+}
@@ -1,55 +1,534 @@
 
-use std::collections::HashMap;
-use std::process::Command;
-use std::str;
-use std::sync::mpsc::{channel, Receiver};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::thread;
 use std::time::Duration;
 
 use eframe::egui;
 
+mod accent;
+mod battery;
+mod broadcast;
+mod config;
+mod control;
+mod crash;
+mod devices;
+mod equalizer;
+mod exec;
+mod hooks;
+mod hotkeys;
+mod keybindings;
+mod monitor;
+mod mpris;
+mod pactl;
+mod peak;
+mod tray;
+mod windows;
+
+use accent::SystemAccent;
+use battery::Batteries;
+use broadcast::Broadcast;
+use config::{AutoMuteRule, Config, NoAppsAction, Scene, SceneApp};
+use control::Control;
+use devices::{DeviceWatch, Devices};
+use equalizer::Equalizer;
+use hooks::{Hooks, Levels, StreamLevel};
+use hotkeys::{HotkeyAction, Hotkeys};
+use keybindings::{Binding, BindingAction};
+use mpris::Players;
+use peak::{PeakMeters, SourceMeter};
+use tray::{Tray, TrayEvent};
+use windows::WindowTitles;
+use pactl::{
+    ChannelMode, Props, Sink, VolumeError, parse_sink_inputs, parse_volume_percent, set_app_volume, set_main_volume,
+};
+
+// Time constant (seconds) for easing the displayed system volume towards
+// externally reported values. Small enough to feel instant, large enough to
+// swallow wpctl rounding jitter.
+const VOL_SMOOTHING_SECS: f32 = 0.15;
+
+// Percent the Up/Down keys (and volume media keys) move a volume by.
+const VOLUME_STEP: f32 = 5.0;
+
+// Linear peak "Normalize peak" aims an app's recent loudest moment at (-6 dBFS).
+const NORMALIZE_TARGET_PEAK: f32 = 0.5;
+
+// How long an app's slider takes to glide to an externally changed volume.
+const APP_ANIMATION_SECS: f32 = 0.25;
+
+// An app's slider flashes for FLASH_SECS when another program changes its
+// volume. Changes within USER_ECHO_SECS of the user's own are taken to be the
+// server reporting them back, and moves under FLASH_MIN_CHANGE are rounding.
+const FLASH_SECS: f32 = 1.5;
+const USER_ECHO_SECS: f32 = 2.0;
+const FLASH_MIN_CHANGE: f32 = 0.5;
+
+// A stream that goes away stays listed, greyed out, for this long, so apps
+// that close and reopen streams rapidly don't make the list flicker.
+const LINGER_SECS: f32 = 1.0;
+
+// After a sound server restart, this session's volume and mute changes are
+// reapplied to the apps' new streams as they show up within this many seconds.
+const RESTORE_WINDOW_SECS: f32 = 30.0;
+
+// Hysteresis for the "audio activity" auto-expand: an app counts as active
+// once a tick peaks above ACTIVITY_ON_PEAK (about -26 dBFS) and as idle again
+// once its whole recent history stays below ACTIVITY_OFF_PEAK (-40 dBFS).
+const ACTIVITY_ON_PEAK: f32 = 0.05;
+const ACTIVITY_OFF_PEAK: f32 = 0.01;
+
+// Quantum presets (frames per cycle) offered for the whole graph.
+const QUANTUM_PRESETS: [u32; 4] = [256, 512, 1024, 2048];
+
+// Size (points) of the compact popup shown from the tray icon.
+const POPUP_SIZE: egui::Vec2 = egui::vec2(280.0, 64.0);
+
+// How long closing waits for the command worker to put the equalizer and
+// broadcast sinks away.
+const EXIT_WAIT: Duration = Duration::from_secs(3);
+
 fn main() -> eframe::Result<()> {
+    if std::env::args().nth(1).as_deref() == Some("monitor") {
+        if let Err(e) = monitor::run() {
+            eprintln!("monitor: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if std::env::args().nth(1).as_deref() == Some("focus") {
+        let app = std::env::args().skip(2).collect::<Vec<_>>().join(" ");
+        match control::send(&format!("focus-app {}", app)) {
+            Ok(reply) if reply.starts_with("ok") => return Ok(()),
+            Ok(reply) => eprintln!("focus: {}", reply.trim_start_matches("error ")),
+            Err(e) => eprintln!("focus: {}", e),
+        }
+        std::process::exit(1);
+    }
+
+    // Only print what would change, e.g. for demos; the mixer still reads
+    // and shows the real state.
+    let dry_run = std::env::args().skip(1).any(|arg| arg == "--dry-run");
+    exec::set_dry_run(dry_run);
+
+    crash::install();
+
     let options = eframe::NativeOptions::default();
     eframe::run_native(
-        "App Volume Controller",
+        if dry_run { "App Volume Controller (dry run)" } else { "App Volume Controller" },
         options,
-        Box::new(|_cc| Box::<MyApp>::default()),
+        Box::new(|cc| Box::new(MyApp::new(&cc.egui_ctx))),
     )
 }
 
+/// Where the most recent change to a displayed value came from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ChangeSource {
+    /// The user moved the slider; the display snaps to it.
+    User,
+    /// Reported back by `wpctl` (our own echo, another tool, hardware keys);
+    /// the display eases towards it.
+    External,
+}
+
+/// Which section (and which stream within it) keyboard input applies to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Focus {
+    System,
+    Microphone,
+    Recording(u32), // source-output index
+    App(u32),       // sink-input index
+}
+
+/// What a volume command is for (see `send_volume`).
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum VolumeTarget {
+    System,
+    Mic,
+    Recording(u32), // source-output index
+    App(u32),       // sink-input index
+}
+
+impl VolumeTarget {
+    /// The command worker's key for it: a newer volume for the same target
+    /// replaces one still waiting.
+    fn key(self) -> String {
+        match self {
+            VolumeTarget::System => "volume:system".to_string(),
+            VolumeTarget::Mic => "volume:mic".to_string(),
+            VolumeTarget::Recording(index) => format!("volume:recording:{}", index),
+            VolumeTarget::App(index) => format!("volume:app:{}", index),
+        }
+    }
+
+    fn send(self, vol: f32) -> Result<(), VolumeError> {
+        match self {
+            VolumeTarget::System => set_main_volume(vol),
+            VolumeTarget::Mic => pactl::set_mic_volume(vol),
+            VolumeTarget::Recording(index) => pactl::set_recording_volume(index, vol),
+            VolumeTarget::App(index) => set_app_volume(index, vol),
+        }
+    }
+}
+
+/// How a stream was before the user first touched it this session.
+struct Baseline {
+    volume: Option<f32>,
+    muted: bool,
+}
+
+/// A change the user made this session, waiting for its app's stream to come
+/// back after a sound server restart.
+#[derive(Clone, Debug, PartialEq)]
+struct PendingRestore {
+    app: String,
+    volume: Option<f32>,
+    muted: bool,
+}
+
+/// Values this session put on a stream that the server may not report yet:
+/// a volume still with the command worker (`queued_volumes`), or a change
+/// being put back after a server restart. They win over saved and live values.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+struct SessionOverride {
+    volume: Option<f32>,
+    muted: Option<bool>,
+}
+
+/// A stream that just went away, kept on screen for `LINGER_SECS`.
+struct Lingering {
+    props: Props,
+    volume: Option<f32>,
+    since: std::time::Instant,
+}
+
+/// The compact system-volume popup opened from the tray icon.
+struct Popup {
+    pos: egui::Pos2,
+    seen_focus: bool, // it has had focus once, so losing it means click-away
+}
+
+/// Streams being moved in the background by "Send everything here" (or its
+/// undo); `done` counts the moves finished so far.
+struct RouteMove {
+    done: Arc<AtomicUsize>,
+    total: usize,
+    failure: Arc<Mutex<Option<VolumeError>>>, // the last move that failed
+}
+
+/// Where everything played before "Send everything here", for its undo.
+struct RouteUndo {
+    sink: String, // the sink everything was sent to
+    default_sink: Option<String>,
+    streams: Vec<(u32, String)>, // sink-input index -> sink name
+}
+
+/// One change applying a scene makes. `scene_plan` lists them for the
+/// preview, and `apply_scene_steps` carries out that same list.
+#[derive(Clone, Debug, PartialEq)]
+enum SceneStep {
+    CardProfile { card: String, profile: String },
+    DefaultSink(String),
+    Move { pid: u32, sink: String },
+    Volume { pid: u32, volume: f32 },
+    Mute { pid: u32, muted: bool },
+}
+
+/// A scene about to be applied, with exactly what that would change.
+struct ScenePreview {
+    name: String,
+    steps: Vec<SceneStep>,
+    missing: Vec<String>, // the scene's apps that aren't running
+    since: std::time::Instant,
+}
+
+/// A destructive button waiting for its "Sure?" to be answered (see
+/// `confirm_button`).
+struct Confirmation {
+    id: egui::Id,
+    since: std::time::Instant,
+}
+
+/// Streams of other sessions' sound servers, or why a server couldn't be read.
+type OtherSessions = Vec<(PathBuf, Result<HashMap<u32, Props>, String>)>;
+
+/// One poll of the background thread.
+struct Snapshot {
+    apps: HashMap<u32, Props>,
+    recordings: HashMap<u32, Props>,
+    sinks: Vec<Sink>,
+    default_sink: Option<String>,
+    system: Result<(f32, bool), VolumeError>, // default sink volume and mute
+    mic: Option<(f32, bool)>,                 // default source volume and mute, None without a mic
+    wpctl_available: bool,
+    reachable: bool, // pactl got an answer from the server
+    other_sessions: OtherSessions, // empty unless enabled
+    error: Option<VolumeError>,    // the first read that failed; its list is left empty
+}
+
 struct MyApp {
     apps: HashMap<u32, HashMap<String, String>>, // pid -> {prop -> val}
     per_app_volumes: HashMap<u32, f32>,          // pid -> volume in percent
-    vol: f32,                                    // main vol
+    per_app_peak: HashMap<u32, f32>,             // pid -> latest linear peak, 0..=1
+    per_app_muted: HashMap<u32, bool>,           // pid -> muted
+    app_sources: HashMap<u32, ChangeSource>,     // pid -> where its volume last changed from
+    app_user_changes: HashMap<u32, std::time::Instant>, // pid -> when the user last set its volume
+    app_flashes: HashMap<u32, std::time::Instant>, // pid -> when another program last changed it
+    sink_mutes: HashMap<u32, HashMap<u32, bool>>, // sink -> {pid -> muted before bulk mute}
+    auto_muted: HashSet<u32>,                    // pids muted by an auto-mute rule
+    mute_followers: HashMap<u32, bool>,          // pid -> muted before following the system mute
+    auto_mute_edit: (String, String),            // new rule being typed: mute, when
+    route_move: Option<RouteMove>,
+    route_undo: Option<RouteUndo>,
+    session_apps: HashMap<u32, Baseline>,        // pid -> state before this session's first change
+    session_system: Option<f32>,                 // system vol before this session's first change
+    pending_restores: Vec<PendingRestore>,       // session changes to reapply after a server restart
+    restart_seen: Option<std::time::Instant>,    // when the last server restart was noticed
+    lingering: HashMap<u32, Lingering>,          // pid -> stream that just went away
+    queued_volumes: BTreeMap<VolumeTarget, f32>, // latest volume per slider still with the command worker
+    pending_boosts: BTreeMap<u32, f32>,          // pid -> saved volume above 100%, held at 100% until confirmed
+    recordings: HashMap<u32, Props>,             // source-output index -> {prop -> val}
+    system_sounds: HashMap<u32, Props>,          // sink-input index -> event sound stream, not in `apps`
+    other_sessions: OtherSessions,               // read-only streams of other sessions
+    show_other_sessions: Arc<AtomicBool>,        // shared with the polling thread
+    per_recording_volumes: HashMap<u32, f32>,    // source-output index -> volume in percent
+    vol: f32,                                    // main vol, as displayed
+    vol_target: f32,                             // main vol, exact last known value
+    vol_source: ChangeSource,
+    sys_muted: bool,
+    mic_vol: Option<f32>,                        // default source vol, None without a mic
+    mic_muted: bool,
+    mic_mute_restored: bool,                     // the saved mic mute was applied to the first mic seen
+    mic_name: Option<String>,                    // default source, as wpctl names it (see `apply_devices`)
+    has_output: bool,                            // whether a default sink exists at all
+    backend: pactl::BackendMode,                 // shown as a badge unless Full
+    backend_error: Option<pactl::VolumeError>,   // last failed command, until dismissed
+    failure_tx: Sender<VolumeError>,             // for jobs posted to the command worker
+    failure_rx: Receiver<VolumeError>,           // their failures, for `report`
+    sinks: Vec<Sink>,
+    default_sink: Option<String>,
+    card_profiles: BTreeMap<String, String>,     // card name -> active profile, from the device lists
+    scene_name_edit: String,                     // name for "Save current scene"
+    scene_preview: Option<ScenePreview>,         // scene whose changes await confirmation
+    confirming: Option<Confirmation>,            // destructive button awaiting its "Sure?"
+    group_name_edit: String,                     // name for a new custom group
+    label_property_edit: String,                 // new entry for the label order list
+    renaming: Option<(String, String)>,          // sink name being renamed, edit buffer
+    remember_prompt: Option<(String, f32)>,      // app key, volume offered for new streams
+    mute_confirm: Option<u32>,                   // pid whose mute awaits confirmation
+    latency_edits: HashMap<u32, u32>,            // pid -> latency (frames) being edited
+    clock_settings: Arc<Mutex<Option<Result<pactl::ClockSettings, VolumeError>>>>, // filled by the worker
+    clock_requested: bool,                       // a read of `clock_settings` was posted
+    role_overrides: HashMap<u32, Option<String>>, // pid -> media role set here (None: cleared)
+    active_apps: HashSet<u32>,                   // pids currently making sound
+    compact_expanded: HashSet<u32>,              // pids opened to full controls in the compact view
+    selected_apps: HashSet<u32>,                 // pids picked with Ctrl+click, adjusted together
+    seen_streams: HashMap<u32, Option<String>>,  // pid -> sink it was on at the last apply_saved_volumes
+    loaded: bool,                                // at least one snapshot has arrived
+    had_apps: bool,                              // apps were playing at the last frame
+    hidden_without_apps: bool,                   // hidden to the tray by `when_no_apps`
+    collapsed_without_apps: bool,                // folded to the system slider by `when_no_apps`
+    config: Config,
+    focus: Option<Focus>,
+    scroll_to_focus: bool,
     last_update: std::time::Instant,
-    update_rx: Receiver<HashMap<u32, HashMap<String, String>>>,
+    update_rx: Receiver<Snapshot>,
+    ctx: egui::Context,                          // for repaints from the command worker
+    device_watch: DeviceWatch,                   // device lists alone, on demand and on device events
+    equalizer: Equalizer,
+    broadcast: Broadcast,
+    peaks: PeakMeters,
+    batteries: Batteries,
+    window_titles: WindowTitles,
+    players: Players,
+    system_accent: SystemAccent,
+    base_visuals: egui::Visuals,                 // the built-in look, before any accent
+    applied_accent: Option<[u8; 3]>,             // accent currently in the visuals
+    monitor_meter: SourceMeter,                  // the default sink's monitor, when shown
+    monitor_level: f32,                          // its latest linear peak, 0..=1
+    hotkeys: Hotkeys,
+    hooks: Hooks,
+    hook_command_edit: String,                   // command being typed in Settings
+    panic_key_edit: String,                      // combo being typed in Settings
+    panic_key_error: Option<String>,             // why the panic key couldn't be grabbed
+    keybindings: Vec<Binding>,                   // parsed from config.keybindings
+    keybinding_errors: Vec<String>,              // entries left out, and why
+    tray: Tray,
+    control: Result<Option<Control>, String>, // running socket, or why it couldn't start
+    popup: Option<Popup>,
+    locked: bool,                                // all controls read-only
+    pin_entry: String,                           // PIN typed into the lock banner
+    pin_rejected: bool,                          // the last unlock attempt had the wrong PIN
+    new_pin_edit: String,                        // PIN being set in Settings
 }
 
-impl Default for MyApp {
-    fn default() -> Self {
-        let vol = match get_system_volume() {
-            Some(v) => v,
-            None => 0.0,
-        };
+impl MyApp {
+    fn new(ctx: &egui::Context) -> Self {
+        let config = Config::load();
+        let control = if config.control_socket { Control::start(ctx).map(Some) } else { Ok(None) };
+        pactl::set_integer_volumes(config.integer_volumes);
+        exec::set_min_interval(Duration::from_millis(config.min_command_interval_ms.into()));
+        let show_other_sessions = Arc::new(AtomicBool::new(config.show_other_sessions));
+        let (keybindings, keybinding_errors) = keybindings::parse(&config.keybindings);
+        let locked = config.lock_on_start;
+        let mut hotkeys = Hotkeys::new(ctx);
+        let panic_key_error = hotkeys.set_panic_key(config.panic_key.as_deref()).err();
+        let panic_key_edit = config.panic_key.clone().unwrap_or_default();
+        let hook_command_edit = config.hook_command.clone().unwrap_or_default();
+        let window_titles = WindowTitles::new(config.show_window_titles);
+
+        let tray = Tray::new(ctx);
+        // Only without a tray would there be no way to bring the window up.
+        if config.start_hidden && tray.available() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
+        }
 
         let (tx, rx) = channel();
+        let (failure_tx, failure_rx) = channel();
+        // Card profiles only come with the device lists: read them now rather
+        // than at the first device event.
+        let device_watch = DeviceWatch::new(ctx);
+        device_watch.refresh();
 
         // Spawn a background thread that polls `pactl` every second and sends results.
-        thread::spawn(move || loop {
-            let apps = parse_sink_inputs();
-            // best-effort send; if receiver was dropped, stop the thread
-            if tx.send(apps).is_err() {
-                break;
+        let poll_other_sessions = Arc::clone(&show_other_sessions);
+        thread::spawn(move || {
+            let wpctl_available = pactl::command_available("wpctl");
+            loop {
+                let other_sessions = if poll_other_sessions.load(Ordering::Relaxed) {
+                    pactl::other_servers()
+                        .into_iter()
+                        .map(|server| {
+                            let streams = pactl::server_sink_inputs(&server);
+                            (server, streams)
+                        })
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                let mut error = None;
+                let sinks = noted(pactl::list_sinks(), &mut error);
+                // No sinks at all is rare enough to be worth asking why.
+                let reachable = !sinks.is_empty() || pactl::server_reachable();
+                let snapshot = Snapshot {
+                    apps: noted(parse_sink_inputs(), &mut error),
+                    recordings: noted(pactl::parse_source_outputs(), &mut error),
+                    sinks,
+                    default_sink: noted(pactl::default_sink(), &mut error),
+                    // Left out of `error`: there is no default sink or mic to
+                    // read without a device.
+                    system: pactl::get_system_state(),
+                    mic: pactl::get_mic_state().ok(),
+                    wpctl_available,
+                    reachable,
+                    other_sessions,
+                    error,
+                };
+                // best-effort send; if receiver was dropped, stop the thread
+                if tx.send(snapshot).is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_secs(1));
             }
-            thread::sleep(Duration::from_secs(1));
         });
 
         Self {
             apps: HashMap::new(),
             per_app_volumes: HashMap::new(),
-            vol,
+            per_app_peak: HashMap::new(),
+            per_app_muted: HashMap::new(),
+            app_sources: HashMap::new(),
+            app_user_changes: HashMap::new(),
+            app_flashes: HashMap::new(),
+            sink_mutes: HashMap::new(),
+            auto_muted: HashSet::new(),
+            mute_followers: HashMap::new(),
+            auto_mute_edit: (String::new(), String::new()),
+            route_move: None,
+            route_undo: None,
+            session_apps: HashMap::new(),
+            session_system: None,
+            pending_restores: Vec::new(),
+            restart_seen: None,
+            lingering: HashMap::new(),
+            queued_volumes: BTreeMap::new(),
+            pending_boosts: BTreeMap::new(),
+            recordings: HashMap::new(),
+            system_sounds: HashMap::new(),
+            other_sessions: Vec::new(),
+            show_other_sessions,
+            per_recording_volumes: HashMap::new(),
+            vol: 0.0,
+            vol_target: 0.0,
+            vol_source: ChangeSource::External,
+            sys_muted: false,
+            mic_vol: None,
+            mic_muted: false,
+            mic_mute_restored: false,
+            mic_name: None,
+            has_output: false,
+            backend: pactl::BackendMode::Full,
+            backend_error: None,
+            failure_tx,
+            failure_rx,
+            sinks: Vec::new(),
+            default_sink: None,
+            card_profiles: BTreeMap::new(),
+            scene_name_edit: String::new(),
+            scene_preview: None,
+            confirming: None,
+            group_name_edit: String::new(),
+            label_property_edit: String::new(),
+            renaming: None,
+            remember_prompt: None,
+            mute_confirm: None,
+            latency_edits: HashMap::new(),
+            clock_settings: Arc::new(Mutex::new(None)),
+            clock_requested: false,
+            role_overrides: HashMap::new(),
+            active_apps: HashSet::new(),
+            compact_expanded: HashSet::new(),
+            selected_apps: HashSet::new(),
+            seen_streams: HashMap::new(),
+            loaded: false,
+            had_apps: false,
+            hidden_without_apps: false,
+            collapsed_without_apps: false,
+            config,
+            focus: None,
+            scroll_to_focus: false,
             last_update: std::time::Instant::now(),
             update_rx: rx,
+            ctx: ctx.clone(),
+            device_watch,
+            equalizer: Equalizer::new(),
+            broadcast: Broadcast::new(),
+            peaks: PeakMeters::new(),
+            batteries: Batteries::new(),
+            window_titles,
+            players: Players::new(),
+            system_accent: SystemAccent::new(ctx),
+            base_visuals: ctx.style().visuals.clone(),
+            applied_accent: None,
+            monitor_meter: SourceMeter::new(),
+            monitor_level: 0.0,
+            hotkeys,
+            hooks: Hooks::new(),
+            hook_command_edit,
+            panic_key_edit,
+            panic_key_error,
+            keybindings,
+            keybinding_errors,
+            tray,
+            control,
+            popup: None,
+            locked,
+            pin_entry: String::new(),
+            pin_rejected: false,
+            new_pin_edit: String::new(),
         }
     }
 }
@@ -58,205 +537,3796 @@ impl Default for MyApp {
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Drain any background updates and apply the latest state.
-        for apps in self.update_rx.try_iter() {
-            self.apps = apps;
-            // Update per-app volumes from latest apps snapshot
-            self.per_app_volumes.clear();
-            for (pid, data) in &self.apps {
-                if let Some(vol_str) = data.get("Volume") {
-                    if let Some(first_percent) = vol_str.split('/').nth(1) {
-                        if let Some(percent_str) = first_percent.trim().strip_suffix('%') {
-                            if let Ok(percent) = percent_str.trim().parse::<f32>() {
-                                self.per_app_volumes.insert(*pid, percent);
-                            }
-                        }
+        while let Ok(snapshot) = self.update_rx.try_recv() {
+            self.apply_snapshot(snapshot);
+        }
+        while let Ok(e) = self.failure_rx.try_recv() {
+            self.report::<()>(Err(e));
+        }
+        if let Some(devices) = self.device_watch.latest() {
+            self.apply_devices(devices);
+        }
+        self.handle_last_app(ctx);
+        self.lingering.retain(|_, l| l.since.elapsed().as_secs_f32() < LINGER_SECS);
+        if !self.lingering.is_empty() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        self.smooth_volume(ctx);
+        self.apply_accent(ctx);
+
+        self.per_app_peak.extend(self.peaks.poll());
+        self.track_activity(ctx);
+        if let Some(level) = self.monitor_meter.poll() {
+            self.monitor_level = level;
+        }
+        if self.peaks.is_running() || self.monitor_meter.is_running() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        self.handle_keys(ctx);
+        self.handle_media_keys(ctx);
+        self.handle_tray(ctx);
+        self.tray.set_status(tray_status(
+            self.has_output.then_some(self.vol_target),
+            self.sys_muted,
+            self.active_apps.len(),
+        ));
+        self.handle_control(ctx);
+        self.show_popup(ctx);
+        self.show_activity_hud(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("🎧 System Volume Controller");
+                    let changes = self.session_apps.len() + usize::from(self.session_system.is_some());
+                    let timeout = self.config.confirm_timeout_secs;
+                    let enabled = changes > 0 && !self.locked;
+                    let button = egui::Button::new(format!("↺ Clear session changes ({})", changes));
+                    let clear = confirm_button(ui, &mut self.confirming, timeout, egui::Id::new("clear_session"), |ui| {
+                        ui.add_enabled(enabled, button).on_hover_text(
+                            "Put back every volume and mute changed since the mixer started; saved settings are kept",
+                        )
+                    });
+                    if clear {
+                        self.clear_session_changes();
+                    }
+                    if !self.locked && ui.button("🔒 Lock").on_hover_text("Make every control read-only").clicked() {
+                        self.locked = true;
+                    }
+                    if let Some((label, detail)) = self.backend.badge() {
+                        ui.label(egui::RichText::new(label).small().color(ui.visuals().warn_fg_color))
+                            .on_hover_text(detail);
+                    }
+                });
+                if let Some(e) = &self.backend_error {
+                    let dismiss = ui
+                        .horizontal_wrapped(|ui| {
+                            ui.colored_label(ui.visuals().error_fg_color, format!("⚠ {}", e));
+                            ui.small_button("✕").on_hover_text("Dismiss").clicked()
+                        })
+                        .inner;
+                    if dismiss {
+                        self.backend_error = None;
                     }
                 }
-            }
+                if self.locked {
+                    self.lock_banner(ui);
+                }
 
-            // Also refresh system volume when we have new data
-            if let Some(sys_v) = get_system_volume() {
-                self.vol = sys_v;
+                ui.add_enabled_ui(!self.locked, |ui| {
+                    self.system_section(ui);
+                    if self.collapsed_without_apps {
+                        let button = ui
+                            .small_button("⋯ Show everything")
+                            .on_hover_text("No apps are playing; the rest comes back when one starts");
+                        if button.clicked() {
+                            self.collapsed_without_apps = false;
+                        }
+                        return;
+                    }
+                    self.microphone_section(ui);
+
+                    ui.separator();
+
+                    self.outputs_section(ui);
+                    self.recording_section(ui);
+                    self.system_sounds_section(ui);
+                    self.apps_section(ui);
+                    self.custom_groups_section(ui);
+                    self.other_sessions_section(ui);
+
+                    self.scenes_section(ui);
+                    self.settings_section(ui);
+                });
+            });
+        });
+        self.scroll_to_focus = false;
+
+        if ctx.input(|i| i.viewport().focused) == Some(false) {
+            self.config.flush();
+        } else {
+            self.config.save_if_due();
+            if let Some(wait) = self.config.save_due_in() {
+                ctx.request_repaint_after(wait);
             }
-            self.last_update = std::time::Instant::now();
         }
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("🎧 System Volume Controller");
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        exec::post(None, self.equalizer.cleanup());
+        exec::post(None, self.broadcast.cleanup());
+        exec::flush(EXIT_WAIT);
+        self.config.flush();
+        self.peaks.stop_all();
+        self.monitor_meter.stop();
+        self.tray.shutdown();
+        if let Ok(Some(control)) = std::mem::replace(&mut self.control, Ok(None)) {
+            control.stop();
+        }
+    }
+}
 
-        
-            ui.group(|ui| {
-                ui.label("🔊 System Volume:");
-                let slider = ui.add(egui::Slider::new(&mut self.vol, 0.0..=100.0).text("%"));
-                if slider.changed() {
-                    set_main_volume(self.vol);
+impl MyApp {
+    /// Carry out `when_no_apps` as the last app closes, and undo it once an
+    /// app plays again. Only the change counts, so the window can still be
+    /// brought back (or unfolded) while nothing plays.
+    fn handle_last_app(&mut self, ctx: &egui::Context) {
+        let has_apps = !self.apps.is_empty();
+        if has_apps == self.had_apps {
+            return;
+        }
+        self.had_apps = has_apps;
+        if has_apps {
+            if std::mem::take(&mut self.hidden_without_apps) {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+            }
+            self.collapsed_without_apps = false;
+            return;
+        }
+        match self.config.when_no_apps {
+            NoAppsAction::StayOpen => {}
+            NoAppsAction::HideToTray => {
+                if self.tray.available() {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
+                    self.hidden_without_apps = true;
                 }
-            });
+            }
+            NoAppsAction::SystemOnly => self.collapsed_without_apps = true,
+        }
+    }
 
-            ui.separator();
+    /// Take in a poll. When the server answered but a read failed, the error
+    /// is shown and everything stays as it was, rather than every stream of
+    /// the failed list counting as gone. Missing programs have their badge.
+    fn apply_snapshot(&mut self, snapshot: Snapshot) {
+        if snapshot.reachable
+            && let Some(e) = snapshot.error
+            && !matches!(e, VolumeError::BinaryNotFound(_))
+        {
+            self.backend_error = Some(e);
+            return;
+        }
+        let restarted = server_restarted(&self.sinks, &snapshot.sinks);
+        if restarted {
+            self.note_server_restart();
+        }
+        let (system_sounds, apps): (HashMap<u32, Props>, HashMap<u32, Props>) =
+            snapshot.apps.into_iter().partition(|(_, props)| pactl::is_system_sound(props));
+        self.tame_system_sounds(system_sounds);
+        // Indices start over after a restart, so every stream counts as new.
+        let mut new_pids: Vec<u32> =
+            apps.keys().filter(|pid| restarted || !self.apps.contains_key(pid)).copied().collect();
+        new_pids.sort();
 
-            // App Sliders
-            ui.label("🎶 Application Volumes:");
-            // Iterate in a stable, sorted order to avoid widgets jumping positions
-            let mut pids: Vec<u32> = self.apps.keys().cloned().collect();
-            pids.sort();
-            for pid in pids {
-                let props = &self.apps[&pid];
-                let unknown = "Unknown".to_string();
-                // Prefer a more descriptive title when present (tab/page title or media name)
-                let primary = props
-                    .get("media.name")
-                    .or_else(|| props.get("application.name"))
-                    .or_else(|| props.get("application.process.binary"))
-                    .unwrap_or(&unknown);
-                ui.group(|ui| {
-                    ui.label(format!("{} (pid: {})", primary, pid));
-                    // Show secondary info when available and different from primary
-                    if let Some(app_name) = props.get("application.name") {
-                        if app_name != primary {
-                            ui.label(format!("App: {}", app_name));
-                        }
+        self.track_churn(&apps);
+        self.apps = apps;
+        self.recordings = snapshot.recordings;
+        self.other_sessions = snapshot.other_sessions;
+        self.sinks = snapshot.sinks;
+        self.merge_stream_state(&new_pids);
+        if let Some(unload) = self.equalizer.retain_streams(|pid| self.apps.contains_key(&pid)) {
+            exec::post(None, unload);
+        }
+        if let Some(unload) = self.broadcast.retain_streams(|pid| self.apps.contains_key(&pid)) {
+            exec::post(None, unload);
+        }
+        self.latency_edits.retain(|pid, _| self.apps.contains_key(pid));
+        self.role_overrides.retain(|pid, _| self.apps.contains_key(pid));
+        self.session_apps.retain(|pid, _| self.apps.contains_key(pid));
+        self.compact_expanded.retain(|pid| self.apps.contains_key(pid));
+        self.selected_apps.retain(|pid| self.apps.contains_key(pid));
+        self.pending_boosts.retain(|pid, _| self.apps.contains_key(pid));
+        self.mute_confirm = self.mute_confirm.filter(|pid| self.apps.contains_key(pid));
+
+        self.sink_mutes.retain(|sink, _| self.sinks.iter().any(|s| s.index == *sink));
+        self.default_sink = snapshot.default_sink;
+        self.peaks.sync(&self.stream_routing(), &self.sinks);
+        self.has_output = has_default_sink(self.default_sink.as_deref(), &self.sinks);
+        self.sync_monitor_meter();
+        self.per_app_peak.retain(|pid, _| self.apps.contains_key(pid));
+
+        if self.has_output {
+            match snapshot.system {
+                Ok((sys_v, muted)) => {
+                    // A volume still with the worker is newer than what the server has.
+                    if !self.queued_volumes.contains_key(&VolumeTarget::System) {
+                        self.set_external_volume(sys_v);
                     }
-                    if let Some(media_title) = props.get("media.name") {
-                        if media_title != primary {
-                            ui.label(format!("Title: {}", media_title));
-                        }
+                    // The first reading is shown as it is, not eased in from 0.
+                    if !self.loaded {
+                        self.vol = self.vol_target;
                     }
+                    self.sys_muted = muted;
+                }
+                // A missing wpctl already shows as the backend badge.
+                Err(pactl::VolumeError::BinaryNotFound(_)) => {}
+                Err(e) => self.backend_error = Some(e),
+            }
+        }
+        self.sync_mute_followers();
+        if !self.queued_volumes.contains_key(&VolumeTarget::Mic) {
+            self.mic_vol = snapshot.mic.map(|(vol, _)| vol);
+        }
+        self.mic_muted = snapshot.mic.is_some_and(|(_, muted)| muted);
+        self.restore_mic_mute();
+        self.backend =
+            pactl::BackendMode::detect(snapshot.reachable, pactl::pulse_server().as_deref(), snapshot.wpctl_available);
+        self.apply_auto_mute();
+        self.run_hooks();
 
-                    if let Some(vol) = self.per_app_volumes.get_mut(&pid) {
-                        let slider = ui.add(egui::Slider::new(vol, 0.0..=100.0).text("%"));
-                        if slider.changed() {
-                            set_app_volume(pid, *vol);
-                        }
-                    } else {
-                        ui.label("No volume data.");
-                    }
-                });
-                ui.separator();
+        if let Some(focus) = self.focus
+            && !self.focus_order().contains(&focus)
+        {
+            self.focus = None;
+        }
+        self.loaded = true;
+        self.last_update = std::time::Instant::now();
+    }
+
+    /// Take freshly read device lists between full polls. A server restart is
+    /// left to the next poll, which also has to treat every stream as new.
+    fn apply_devices(&mut self, devices: Devices) {
+        if server_restarted(&self.sinks, &devices.sinks) {
+            return;
+        }
+        self.sinks = devices.sinks;
+        self.sink_mutes.retain(|sink, _| self.sinks.iter().any(|s| s.index == *sink));
+        self.default_sink = devices.default_sink;
+        self.peaks.sync(&self.stream_routing(), &self.sinks);
+        self.has_output = has_default_sink(self.default_sink.as_deref(), &self.sinks);
+        self.sync_monitor_meter();
+        self.mic_name = devices.status.default_source().map(|n| n.name.clone());
+        self.card_profiles = devices.cards;
+    }
+
+    /// pid -> name of the sink it plays on.
+    fn stream_routing(&self) -> HashMap<u32, String> {
+        self.apps.iter().filter_map(|(pid, data)| Some((*pid, data.get("Sink")?.clone()))).collect()
+    }
+
+    /// Set new system sound streams to the configured volume.
+    fn tame_system_sounds(&mut self, sounds: HashMap<u32, Props>) {
+        if let Some(vol) = self.config.system_sounds_volume {
+            let new: Vec<u32> =
+                sounds.keys().filter(|index| !self.system_sounds.contains_key(index)).copied().collect();
+            for index in new {
+                self.send_volume(VolumeTarget::App(index), vol);
             }
-        });
+        }
+        self.system_sounds = sounds;
     }
-}
 
-fn set_main_volume(vol: f32) {
-    let _ = Command::new("wpctl")
-        .args(["set-volume", "@DEFAULT_AUDIO_SINK@", &format!("{:.2}%", vol)])
-        .output();
-}
+    /// The sound server went away or restarted: remember this session's
+    /// changes by app, to put back on the new streams (`restore_overrides`).
+    /// Everything kept per stream index is stale now.
+    fn note_server_restart(&mut self) {
+        let mut pids: Vec<u32> = self.session_apps.keys().copied().collect();
+        pids.sort();
+        for pid in pids {
+            let Some(app) = self.apps.get(&pid).and_then(pactl::app_key) else {
+                continue;
+            };
+            self.pending_restores.push(PendingRestore {
+                app: app.to_string(),
+                volume: self.per_app_volumes.get(&pid).copied(),
+                muted: self.per_app_muted.get(&pid).copied().unwrap_or(false),
+            });
+        }
+        self.restart_seen = Some(std::time::Instant::now());
+        self.session_apps.clear();
+        self.seen_streams.clear();
+        self.sink_mutes.clear();
+        self.auto_muted.clear();
+        self.mute_followers.clear();
+    }
+
+    /// Settle what each stream shows after a snapshot: session overrides,
+    /// then saved volumes, then what the server reports (`merge_layers`).
+    fn merge_stream_state(&mut self, new_pids: &[u32]) {
+        // Volumes the worker has sent are up to the server again.
+        self.queued_volumes.retain(|target, _| exec::pending(&target.key()));
+        self.refresh_volumes();
+        let saved = self.apply_saved_volumes();
+        let mut overrides = self.restore_overrides(new_pids);
+        for (target, volume) in &self.queued_volumes {
+            // System sounds are sink inputs too, but not in `apps`.
+            if let VolumeTarget::App(pid) = target
+                && self.apps.contains_key(pid)
+            {
+                overrides.entry(*pid).or_default().volume = Some(*volume);
+            }
+        }
+        let (volumes, muted) = merge_layers(&self.per_app_volumes, &self.per_app_muted, &saved, &overrides);
+        self.per_app_volumes = volumes;
+        self.per_app_muted = muted;
+    }
 
-fn set_app_volume(index: u32, vol: f32) {
-    //println!("the u32 {} and the vol {} ",index,vol);
+    /// Reapply changes from before a server restart to the new streams of the
+    /// same apps, until `RESTORE_WINDOW_SECS` after it.
+    fn restore_overrides(&mut self, new_pids: &[u32]) -> HashMap<u32, SessionOverride> {
+        if self.restart_seen.is_some_and(|t| t.elapsed().as_secs_f32() > RESTORE_WINDOW_SECS) {
+            self.pending_restores.clear();
+            self.restart_seen = None;
+        }
+        let mut restored = HashMap::new();
+        for (pid, restore) in match_restores(&mut self.pending_restores, &self.apps, new_pids) {
+            if let Some(volume) = restore.volume {
+                self.change_app_volume(pid, volume);
+            }
+            if self.per_app_muted.get(&pid).copied().unwrap_or(false) != restore.muted {
+                self.change_app_mute(pid, restore.muted);
+            }
+            restored.insert(pid, SessionOverride { volume: restore.volume, muted: Some(restore.muted) });
+        }
+        restored
+    }
 
-   let id_str=index.to_string();
+    /// Keep streams that vanished since the last snapshot around as
+    /// `lingering`. When the same app opens a new stream meanwhile, it takes
+    /// the old one's place instead, keyboard focus included.
+    fn track_churn(&mut self, apps: &HashMap<u32, Props>) {
+        let now = std::time::Instant::now();
+        for (pid, props) in &self.apps {
+            if !apps.contains_key(pid) {
+                let volume = self.per_app_volumes.get(pid).copied();
+                self.lingering.insert(*pid, Lingering { props: props.clone(), volume, since: now });
+            }
+        }
 
-   let _ = Command::new("pactl")
-        .args(&["set-sink-input-volume", &id_str, &format!("{}%",vol)])
-        .output();
+        let mut new: Vec<u32> = apps.keys().filter(|pid| !self.apps.contains_key(pid)).copied().collect();
+        new.sort();
+        for pid in new {
+            let Some(app) = pactl::app_key(&apps[&pid]) else {
+                continue;
+            };
+            let old = self.lingering.iter().find(|(_, l)| pactl::app_key(&l.props) == Some(app)).map(|(old, _)| *old);
+            if let Some(old) = old {
+                self.lingering.remove(&old);
+                if self.focus == Some(Focus::App(old)) {
+                    self.focus = Some(Focus::App(pid));
+                }
+            }
+        }
+        self.lingering.retain(|pid, _| !apps.contains_key(pid));
     }
 
+    /// Mute the targets of auto-mute rules whose trigger app plays, and unmute
+    /// them once it stops. Streams the user had muted already are left alone.
+    fn apply_auto_mute(&mut self) {
+        let playing = |pid: u32| {
+            let props = &self.apps[&pid];
+            props.get("Corked").is_none_or(|c| c != "yes")
+                && !self.per_app_muted.get(&pid).copied().unwrap_or(false)
+                && self.peaks.recent_peak(pid).is_none_or(|p| p >= ACTIVITY_OFF_PEAK)
+        };
+        let targets = auto_mute_targets(&self.config.auto_mute, &self.apps, playing);
 
+        for pid in &targets {
+            if !self.auto_muted.contains(pid) && !self.per_app_muted.get(pid).copied().unwrap_or(false) {
+                self.auto_muted.insert(*pid);
+                self.per_app_muted.insert(*pid, true);
+                let pid = *pid;
+                self.post(None, move || pactl::set_app_mute(pid, true));
+            }
+        }
+        let released: Vec<u32> = self.auto_muted.iter().filter(|pid| !targets.contains(pid)).copied().collect();
+        for pid in released {
+            self.auto_muted.remove(&pid);
+            if self.apps.contains_key(&pid) {
+                self.per_app_muted.insert(pid, false);
+                self.post(None, move || pactl::set_app_mute(pid, false));
+            }
+        }
+    }
 
+    /// Hand the polled state to the hook command, if one is configured.
+    fn run_hooks(&mut self) {
+        let Some(command) = self.config.hook_command.as_deref() else {
+            return;
+        };
+        let streams = self
+            .apps
+            .iter()
+            .map(|(pid, props)| {
+                let stream = StreamLevel {
+                    name: pactl::resolve_display_name(props, &self.config.label_properties).to_string(),
+                    volume: self.per_app_volumes.get(pid).copied(),
+                    muted: self.per_app_muted.get(pid).copied().unwrap_or(false),
+                };
+                (*pid, stream)
+            })
+            .collect();
+        let levels = Levels {
+            system: self.has_output.then_some((self.vol_target, self.sys_muted)),
+            streams,
+        };
+        self.hooks.observe(command, self.config.hook_volume_threshold, levels);
+    }
 
-fn get_system_volume() -> Option<f32> {
-    let output = Command::new("wpctl")
-        .args(["get-volume", "@DEFAULT_AUDIO_SINK@"])
-        .output()
-        .expect("failed to get volume");
+    /// Put the chosen accent (custom, else the desktop's) into the visuals,
+    /// or the built-in one back when there is none.
+    fn apply_accent(&mut self, ctx: &egui::Context) {
+        let system = self.system_accent.get().filter(|_| self.config.follow_system_accent);
+        let accent = self.config.accent_color.or(system);
+        if accent == self.applied_accent {
+            return;
+        }
+        self.applied_accent = accent;
+        let mut visuals = self.base_visuals.clone();
+        if let Some([r, g, b]) = accent {
+            let color = egui::Color32::from_rgb(r, g, b);
+            visuals.selection.bg_fill = color;
+            visuals.hyperlink_color = color;
+            visuals.widgets.hovered.bg_stroke.color = color;
+        }
+        ctx.set_visuals(visuals);
+    }
 
-    if output.status.success() {
-        let stdout = str::from_utf8(&output.stdout).unwrap_or("invalid UTF-8");
-        let parts: Vec<&str> = stdout.trim().split_whitespace().collect();
+    /// Keep the monitor meter on the default sink while it's shown.
+    fn sync_monitor_meter(&mut self) {
+        let sink = self.default_sink.as_deref().filter(|_| self.config.show_monitor_level && self.has_output);
+        match sink {
+            None => self.monitor_meter.stop(),
+            Some(sink) if self.monitor_meter.sink() != Some(sink) => {
+                match self.sinks.iter().find(|s| s.name == sink).and_then(|s| s.monitor_source.as_deref()) {
+                    Some(source) => self.monitor_meter.watch(sink, source),
+                    None => self.monitor_meter.stop(),
+                }
+            }
+            Some(_) => {}
+        }
+        if !self.monitor_meter.is_running() {
+            self.monitor_level = 0.0;
+        }
+    }
 
-        if let Some(volume_str) = parts.last() {
-            if let Ok(volume) = volume_str.parse::<f32>() {
-           
-                return Some(volume * 100.0); // as percentage
+    /// Re-derive the displayed stream volumes from the last parsed snapshot.
+    fn refresh_volumes(&mut self) {
+        let mode = self.config.channel_mode;
+        let volumes = |streams: &HashMap<u32, Props>| -> HashMap<u32, f32> {
+            streams
+                .iter()
+                .filter_map(|(index, data)| Some((*index, parse_volume_percent(data.get("Volume")?, mode)?)))
+                .collect()
+        };
+        let previous = std::mem::replace(&mut self.per_app_volumes, volumes(&self.apps));
+        self.app_sources.retain(|pid, _| self.apps.contains_key(pid));
+        self.app_user_changes.retain(|pid, _| self.apps.contains_key(pid));
+        self.app_flashes.retain(|pid, _| self.apps.contains_key(pid));
+        let now = std::time::Instant::now();
+        for (pid, vol) in &self.per_app_volumes {
+            let Some(old) = previous.get(pid).filter(|old| *old != vol) else {
+                continue;
+            };
+            self.app_sources.insert(*pid, ChangeSource::External);
+            let echo = self
+                .app_user_changes
+                .get(pid)
+                .is_some_and(|t| t.elapsed().as_secs_f32() < USER_ECHO_SECS);
+            if !echo && (old - vol).abs() >= FLASH_MIN_CHANGE {
+                self.app_flashes.insert(*pid, now);
             }
         }
+        self.per_recording_volumes = volumes(&self.recordings);
+        self.per_app_muted = self
+            .apps
+            .iter()
+            .map(|(pid, data)| (*pid, data.get("Mute").is_some_and(|m| m == "yes")))
+            .collect();
+    }
 
-        eprintln!("Failed to parse volume from output: {}", stdout);
-    } else {
-        eprintln!(
-            "Error: {}",
-            str::from_utf8(&output.stderr).unwrap_or("unknown error")
-        );
+    /// Volume (percent) of the sink a stream plays on.
+    fn sink_volume(&self, props: &Props) -> Option<f32> {
+        parse_volume_percent(&self.stream_sink(props)?.volume, self.config.channel_mode)
     }
 
-    None
-}
+    /// The sink a sink input plays on.
+    fn stream_sink(&self, props: &Props) -> Option<&Sink> {
+        sink_of(props, &self.sinks)
+    }
+
+    /// Sink-input indices currently routed to sink `sink`.
+    fn streams_on_sink(&self, sink: u32) -> Vec<u32> {
+        let sink = sink.to_string();
+        let mut pids: Vec<u32> = self
+            .apps
+            .iter()
+            .filter(|(_, props)| props.get("Sink") == Some(&sink))
+            .map(|(pid, _)| *pid)
+            .collect();
+        pids.sort();
+        pids
+    }
+
+    /// Once a microphone shows up, give it the mute state saved in the config.
+    fn restore_mic_mute(&mut self) {
+        if self.mic_mute_restored || self.mic_vol.is_none() {
+            return;
+        }
+        self.mic_mute_restored = true;
+        if let Some(muted) = self.config.mic_muted
+            && muted != self.mic_muted
+        {
+            self.change_mic_mute(muted);
+        }
+    }
+
+    fn change_mic_mute(&mut self, muted: bool) {
+        self.mic_muted = muted;
+        self.post(None, move || pactl::set_mic_mute(muted));
+        if self.config.mic_muted.is_some() {
+            self.config.mic_muted = Some(muted);
+            self.config.save_later();
+        }
+    }
+
+    /// The user moved the system volume; the display snaps to it.
+    fn change_system_volume(&mut self, vol: f32) {
+        self.session_system.get_or_insert(self.vol_target);
+        self.vol_target = vol;
+        self.vol = vol;
+        self.vol_source = ChangeSource::User;
+        self.send_volume(VolumeTarget::System, vol);
+    }
+
+    fn change_system_mute(&mut self, muted: bool) {
+        self.sys_muted = muted;
+        self.post(None, move || pactl::set_main_mute(muted));
+        self.sync_mute_followers();
+    }
+
+    /// While the system is muted, keep the streams of apps set to follow it
+    /// muted too; once it isn't (or an app stops following), put each back
+    /// the way it was. Like auto-mute this isn't a session change.
+    fn sync_mute_followers(&mut self) {
+        let follows = |props: &Props| {
+            self.sys_muted && pactl::app_key(props).is_some_and(|app| self.config.follows_system_mute(app))
+        };
+        let joining: Vec<u32> = self
+            .apps
+            .iter()
+            .filter(|(pid, props)| !self.mute_followers.contains_key(pid) && follows(props))
+            .map(|(pid, _)| *pid)
+            .collect();
+        let leaving: Vec<u32> = self
+            .mute_followers
+            .keys()
+            .filter(|pid| !self.apps.get(pid).is_some_and(follows))
+            .copied()
+            .collect();
 
-fn parse_sink_inputs() -> HashMap<u32, HashMap<String, String>> {
-    let output = Command::new("pactl")
-        .args(&["list", "sink-inputs"])
-        .output()
-        .expect("Failed to execute pactl");
+        for pid in joining {
+            let muted = self.per_app_muted.get(&pid).copied().unwrap_or(false);
+            self.mute_followers.insert(pid, muted);
+            if !muted {
+                self.per_app_muted.insert(pid, true);
+                self.post(None, move || pactl::set_app_mute(pid, true));
+            }
+        }
+        for pid in leaving {
+            let was_muted = self.mute_followers.remove(&pid).unwrap_or(true);
+            if !was_muted && self.apps.contains_key(&pid) {
+                self.per_app_muted.insert(pid, false);
+                self.post(None, move || pactl::set_app_mute(pid, false));
+            }
+        }
+    }
 
-    let stdout = str::from_utf8(&output.stdout).expect("Invalid UTF-8 output");
+    /// The user set an app's volume.
+    fn change_app_volume(&mut self, pid: u32, vol: f32) {
+        self.note_session_change(pid);
+        self.app_sources.insert(pid, ChangeSource::User);
+        self.app_user_changes.insert(pid, std::time::Instant::now());
+        self.app_flashes.remove(&pid);
+        self.per_app_volumes.insert(pid, vol);
+        self.send_volume(VolumeTarget::App(pid), vol);
+    }
 
-    let mut result: HashMap<u32, HashMap<String, String>> = HashMap::new();
-    let mut current_id: Option<u32> = None;
+    /// Set a volume the user is changing. It is posted under the target's key
+    /// (`VolumeTarget::key`), replacing any older one for the same slider still
+    /// waiting for the worker, so a drag on a slow machine sends only the
+    /// latest volume when its turn comes.
+    fn send_volume(&mut self, target: VolumeTarget, vol: f32) {
+        self.queued_volumes.insert(target, vol);
+        self.post(Some(target.key()), move || target.send(vol));
+    }
 
-    for line in stdout.lines() {
-        let trimmed = line.trim_start();
+    /// Hand a change to the command worker (`exec`), which runs it off the UI
+    /// thread; with a `key`, it replaces a waiting change with the same key.
+    /// A failure comes back through `failure_rx` to be shown like `report`'s.
+    fn post(&self, key: Option<String>, change: impl FnOnce() -> Result<(), VolumeError> + Send + 'static) {
+        let failures = self.failure_tx.clone();
+        let ctx = self.ctx.clone();
+        exec::post(key, move || {
+            if let Err(e) = change() {
+                let _ = failures.send(e);
+                ctx.request_repaint();
+            }
+        });
+    }
 
-        if let Some(id_str) = trimmed.strip_prefix("Sink Input #") {
-            if let Ok(id) = id_str.trim().parse::<u32>() {
-                current_id = Some(id);
-                result.insert(id, HashMap::new());
+    /// Show a failed command above the sections until it is dismissed. Dry
+    /// runs fail every change on purpose, so those aren't shown.
+    fn report<T>(&mut self, result: Result<T, VolumeError>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(VolumeError::DryRun) => None,
+            Err(e) => {
+                self.backend_error = Some(e);
+                None
             }
         }
+    }
+
+    /// The user muted or unmuted an app.
+    fn change_app_mute(&mut self, pid: u32, muted: bool) {
+        self.note_session_change(pid);
+        self.per_app_muted.insert(pid, muted);
+        self.post(None, move || pactl::set_app_mute(pid, muted));
+    }
+
+    /// Remember how `pid` was before the first change this session.
+    fn note_session_change(&mut self, pid: u32) {
+        if self.session_apps.contains_key(&pid) {
+            return;
+        }
+        let baseline = Baseline {
+            volume: self.per_app_volumes.get(&pid).copied(),
+            muted: self.per_app_muted.get(&pid).copied().unwrap_or(false),
+        };
+        self.session_apps.insert(pid, baseline);
+    }
 
-        if let Some((key, value)) = trimmed.split_once(" = ") {
-            if let Some(id) = current_id {
-                result
-                    .get_mut(&id)
-                    .unwrap()
-                    .insert(key.to_string(), value.trim_matches('"').to_string());
+    /// Undo everything changed this session: apps go back to their saved
+    /// volume (or, without one, to how they were before), the system volume
+    /// to where it started. The config file is not touched.
+    fn clear_session_changes(&mut self) {
+        for (pid, baseline) in std::mem::take(&mut self.session_apps) {
+            let Some(props) = self.apps.get(&pid) else {
+                continue;
+            };
+            let saved = pactl::app_key(props).and_then(|app| self.config.app_volume(app));
+            if let Some(vol) = saved.or(baseline.volume) {
+                self.per_app_volumes.insert(pid, vol);
+                self.send_volume(VolumeTarget::App(pid), vol);
+            }
+            if self.per_app_muted.get(&pid) != Some(&baseline.muted) {
+                self.per_app_muted.insert(pid, baseline.muted);
+                self.post(None, move || pactl::set_app_mute(pid, baseline.muted));
             }
         }
 
-        if trimmed.starts_with("Volume:") {
-            if let Some(id) = current_id {
-                result
-                    .get_mut(&id)
-                    .unwrap()
-                    .insert("Volume".to_string(), trimmed["Volume:".len()..].trim().to_string());
+        if let Some(vol) = self.session_system.take() {
+            self.vol_target = vol;
+            self.vol = vol;
+            self.send_volume(VolumeTarget::System, vol);
+        }
+    }
+
+    /// Mute every stream on `sink`, remembering how each one was so that
+    /// `restore_sink_streams` can put them back.
+    fn mute_sink_streams(&mut self, sink: u32) {
+        let mut prior = HashMap::new();
+        for pid in self.streams_on_sink(sink) {
+            prior.insert(pid, self.per_app_muted.get(&pid).copied().unwrap_or(false));
+            self.change_app_mute(pid, true);
+        }
+        self.sink_mutes.insert(sink, prior);
+    }
+
+    /// Undo `mute_sink_streams`. Streams that joined the sink since are
+    /// unmuted; streams that left it or vanished are not touched.
+    fn restore_sink_streams(&mut self, sink: u32) {
+        let prior = self.sink_mutes.remove(&sink).unwrap_or_default();
+        for pid in self.streams_on_sink(sink) {
+            self.change_app_mute(pid, prior.get(&pid).copied().unwrap_or(false));
+        }
+    }
+
+    /// Make `sink` the default and move every stream onto it, remembering the
+    /// previous routing for `undo_send_all`. Streams inside the mixer's own
+    /// sinks (equalizer, broadcast) stay where they are.
+    fn send_all_to(&mut self, sink: &str) {
+        let mut streams = Vec::new();
+        let mut moves = Vec::new();
+        for (pid, props) in &self.apps {
+            let Some(current) = self.stream_sink(props) else {
+                continue;
+            };
+            if current.name == sink || current.name.starts_with(pactl::OWN_SINK_PREFIX) {
+                continue;
             }
+            streams.push((*pid, current.name.clone()));
+            moves.push((*pid, sink.to_string()));
         }
+        self.route_undo = Some(RouteUndo {
+            sink: sink.to_string(),
+            default_sink: self.default_sink.clone(),
+            streams,
+        });
+        self.start_route_move(Some(sink.to_string()), moves);
     }
 
-    result
-}
+    /// Put streams that are still around back where they were, and the
+    /// previous default back.
+    fn undo_send_all(&mut self) {
+        let Some(undo) = self.route_undo.take() else {
+            return;
+        };
+        let moves = undo.streams.into_iter().filter(|(pid, _)| self.apps.contains_key(pid)).collect();
+        self.start_route_move(undo.default_sink, moves);
+    }
 
-impl MyApp {
-    fn refresh_apps(&mut self) {
-        self.apps = parse_sink_inputs();
-        self.per_app_volumes.clear();
-        for (pid, data) in &self.apps {
-            if let Some(vol_str) = data.get("Volume") {
-                if let Some(first_percent) = vol_str.split('/').nth(1) {
-                    if let Some(percent_str) = first_percent.trim().strip_suffix('%') {
-                        if let Ok(percent) = percent_str.trim().parse::<f32>() {
-                            self.per_app_volumes.insert(*pid, percent);
-                        }
-                    }
+    /// Make `sink` the default output, showing it as such right away.
+    fn set_default_sink(&mut self, sink: String) {
+        self.default_sink = Some(sink.clone());
+        self.post(None, move || pactl::set_default_sink(&sink));
+    }
+
+    /// Set the default sink, then run the moves (sink-input, sink name) on a
+    /// background thread so many of them don't stall the UI.
+    fn start_route_move(&mut self, default_sink: Option<String>, moves: Vec<(u32, String)>) {
+        if let Some(sink) = default_sink {
+            self.set_default_sink(sink);
+        }
+        let done = Arc::new(AtomicUsize::new(0));
+        let failure = Arc::new(Mutex::new(None));
+        self.route_move = Some(RouteMove {
+            done: Arc::clone(&done),
+            total: moves.len(),
+            failure: Arc::clone(&failure),
+        });
+        thread::spawn(move || {
+            for (pid, sink) in moves {
+                if let Err(e) = pactl::move_sink_input(pid, &sink) {
+                    *failure.lock().unwrap_or_else(|e| e.into_inner()) = Some(e);
                 }
+                // Release: the failure is seen once the count is.
+                done.fetch_add(1, Ordering::Release);
+            }
+        });
+    }
+
+    /// Sections in the order Tab walks through them.
+    fn focus_order(&self) -> Vec<Focus> {
+        let mut order = vec![Focus::System];
+        if self.mic_vol.is_some() {
+            order.push(Focus::Microphone);
+        }
+        let mut recordings: Vec<u32> = self.recordings.keys().copied().collect();
+        recordings.sort();
+        order.extend(recordings.into_iter().map(Focus::Recording));
+        let mut pids: Vec<u32> = self.apps.keys().copied().collect();
+        pids.sort();
+        order.extend(pids.into_iter().map(Focus::App));
+        order
+    }
+
+    /// Tab/Shift-Tab move the focus highlight between sections, Up/Down adjust
+    /// the focused volume by `VOLUME_STEP`, Escape clears the focus.
+    fn handle_keys(&mut self, ctx: &egui::Context) {
+        use egui::{Key, Modifiers};
+
+        // Only changes the view, so it works while locked too.
+        if ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::F2)) {
+            self.toggle_compact_apps();
+        }
+        if self.locked {
+            return;
+        }
+        if !ctx.wants_keyboard_input() {
+            let pressed: Vec<BindingAction> = ctx.input_mut(|i| {
+                self.keybindings
+                    .iter()
+                    .filter(|b| i.consume_shortcut(&b.shortcut))
+                    .map(|b| b.action.clone())
+                    .collect()
+            });
+            for action in pressed {
+                self.run_binding(action);
             }
         }
 
-        // Also refresh system vol
-        let out = Command::new("wpctl")
-            .args(["get-volume", "@DEFAULT_AUDIO_SINK@"])
-            .output()
-            .unwrap();
-        if out.status.success() {
-            let stdout = str::from_utf8(&out.stdout).unwrap_or("");
-            let trimmed = stdout.trim();
-            if let Some(val) = trimmed.split_whitespace().nth(1) {
-                if let Ok(val_f) = val.parse::<f32>() {
-                    self.vol = val_f * 100.0;
+        let (next, prev, escape) = ctx.input_mut(|i| {
+            (
+                i.consume_key(Modifiers::NONE, Key::Tab),
+                i.consume_key(Modifiers::SHIFT, Key::Tab),
+                i.consume_key(Modifiers::NONE, Key::Escape),
+            )
+        });
+
+        if next || prev {
+            let order = self.focus_order();
+            let position = self.focus.and_then(|f| order.iter().position(|o| *o == f));
+            let index = match (position, next) {
+                (None, true) => 0,
+                (None, false) => order.len() - 1,
+                (Some(i), true) => (i + 1) % order.len(),
+                (Some(i), false) => (i + order.len() - 1) % order.len(),
+            };
+            self.focus = Some(order[index]);
+            self.scroll_to_focus = true;
+            // Our sections replace egui's own widget traversal.
+            ctx.memory_mut(|m| {
+                if let Some(id) = m.focused() {
+                    m.surrender_focus(id);
                 }
+            });
+        }
+        if escape {
+            self.focus = None;
+            self.selected_apps.clear();
+        }
+
+        if self.focus.is_some() {
+            let (up, down) = ctx.input_mut(|i| {
+                (
+                    i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                    i.consume_key(Modifiers::NONE, Key::ArrowDown),
+                )
+            });
+            if up {
+                self.adjust_focused(VOLUME_STEP);
+            }
+            if down {
+                self.adjust_focused(-VOLUME_STEP);
             }
         }
     }
+
+    fn run_binding(&mut self, action: BindingAction) {
+        let (app, delta) = match action {
+            BindingAction::ToggleMuteSystem => return self.change_system_mute(!self.sys_muted),
+            BindingAction::RaiseSystem | BindingAction::LowerSystem => {
+                let delta = if action == BindingAction::RaiseSystem { VOLUME_STEP } else { -VOLUME_STEP };
+                return self.step_volumes(&[VolumeTarget::System], delta);
+            }
+            BindingAction::ToggleMuteApp(app) => (app, None),
+            BindingAction::RaiseApp(app) => (app, Some(VOLUME_STEP)),
+            BindingAction::LowerApp(app) => (app, Some(-VOLUME_STEP)),
+        };
+
+        let pids = self.app_pids(&app);
+        match delta {
+            // Mute them all unless they all are already, like the group mute.
+            None => {
+                let mute = pids.iter().any(|pid| !self.per_app_muted.get(pid).copied().unwrap_or(false));
+                for pid in pids {
+                    self.change_app_mute(pid, mute);
+                }
+            }
+            Some(delta) => {
+                let targets: Vec<VolumeTarget> = pids.into_iter().map(VolumeTarget::App).collect();
+                self.step_volumes(&targets, delta);
+            }
+        }
+    }
+
+    /// The wheel turned over `pid`'s slider: step it, or every selected app
+    /// when it is one of several selected, each within its own bounds.
+    fn scroll_apps(&mut self, pid: u32, delta: f32) {
+        let mut pids = vec![pid];
+        if self.selected_apps.len() > 1 && self.selected_apps.contains(&pid) {
+            pids = self.selected_apps.iter().copied().collect();
+            pids.sort();
+        }
+        let targets: Vec<VolumeTarget> = pids.into_iter().map(VolumeTarget::App).collect();
+        self.step_volumes(&targets, delta);
+    }
+
+    /// The process id behind the stream at sink-input `index`, if it says.
+    fn sink_input_pid(&self, index: u32) -> Option<u32> {
+        pactl::process_id(self.apps.get(&index)?)
+    }
+
+    /// The streams (sink-input indices) of process `process_id`.
+    fn sink_inputs_of_process(&self, process_id: u32) -> Vec<u32> {
+        pactl::sink_inputs_of_process(&self.apps, process_id)
+    }
+
+    /// Streams of `app`, matched against application name or binary ignoring
+    /// case, or the streams of process `app` if it's a number; lowest index
+    /// first.
+    fn app_pids(&self, app: &str) -> Vec<u32> {
+        if let Ok(process_id) = app.parse::<u32>() {
+            return self.sink_inputs_of_process(process_id);
+        }
+        let mut pids: Vec<u32> = self
+            .apps
+            .iter()
+            .filter(|(_, props)| stream_is_app(props, app))
+            .map(|(pid, _)| *pid)
+            .collect();
+        pids.sort();
+        pids
+    }
+
+    /// `focus-app` from the control socket: bring up the window on the app's
+    /// (first) slider, ready for the arrow keys.
+    fn handle_control(&mut self, ctx: &egui::Context) {
+        let Ok(Some(control)) = &self.control else {
+            return;
+        };
+        for app in control.poll_focus() {
+            // The socket checked the app exists, but it may have just gone.
+            let Some(pid) = self.app_pids(&app).first().copied() else {
+                eprintln!("focus-app: no stream matches {}", app);
+                continue;
+            };
+            show_main_window(ctx);
+            self.popup = None;
+            self.focus = Some(Focus::App(pid));
+            self.scroll_to_focus = true;
+            if self.config.collapsible_app_groups {
+                set_app_group_open(ctx, pid, true);
+            }
+        }
+    }
+
+    /// Volume media keys. They're grabbed while the window is focused, and
+    /// all the time with the global option; see `hotkeys` for why.
+    fn handle_media_keys(&mut self, ctx: &egui::Context) {
+        let focused = ctx.input(|i| i.focused);
+        self.hotkeys.set_window_focused(focused);
+        self.hotkeys.set_active((focused || self.config.global_media_keys) && !self.locked);
+
+        for action in self.hotkeys.poll() {
+            if !self.has_output {
+                continue;
+            }
+            let delta = match action {
+                HotkeyAction::RaiseVolume => VOLUME_STEP,
+                HotkeyAction::LowerVolume => -VOLUME_STEP,
+                HotkeyAction::ToggleMute => {
+                    self.change_system_mute(!self.sys_muted);
+                    continue;
+                }
+                // Handled on the hotkey thread.
+                HotkeyAction::Panic => continue,
+            };
+            self.step_volumes(&[VolumeTarget::System], delta);
+        }
+    }
+
+    /// Keep `active_apps` up to date; with auto-expand on, expand app groups
+    /// that start making sound and optionally collapse them once quiet.
+    fn track_activity(&mut self, ctx: &egui::Context) {
+        self.active_apps.retain(|pid| self.apps.contains_key(pid));
+        let auto_expand = self.config.collapsible_app_groups && self.config.auto_expand_active;
+
+        for (&pid, &peak) in &self.per_app_peak {
+            let open = if !self.active_apps.contains(&pid) && peak > ACTIVITY_ON_PEAK {
+                self.active_apps.insert(pid);
+                true
+            } else if self.active_apps.contains(&pid)
+                && self.peaks.recent_peak(pid).is_none_or(|p| p < ACTIVITY_OFF_PEAK)
+            {
+                self.active_apps.remove(&pid);
+                if !self.config.auto_collapse_idle {
+                    continue;
+                }
+                false
+            } else {
+                continue;
+            };
+            if !auto_expand {
+                continue;
+            }
+            set_app_group_open(ctx, pid, open);
+        }
+    }
+
+    fn handle_tray(&mut self, ctx: &egui::Context) {
+        for event in self.tray.poll() {
+            match event {
+                TrayEvent::Activate { x, y } if self.config.tray_popup => {
+                    if self.popup.take().is_none() {
+                        let viewport = ctx.input(|i| i.viewport().clone());
+                        let scale = viewport.native_pixels_per_point.unwrap_or(1.0);
+                        let click = egui::pos2(x as f32 / scale, y as f32 / scale);
+                        self.popup = Some(Popup {
+                            pos: popup_position(click, POPUP_SIZE, viewport.monitor_size),
+                            seen_focus: false,
+                        });
+                    }
+                }
+                TrayEvent::Activate { .. } | TrayEvent::ShowWindow => {
+                    self.popup = None;
+                    show_main_window(ctx);
+                }
+                TrayEvent::Quit => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+            }
+        }
+    }
+
+    /// The "what's making sound" HUD: a small always-on-top window listing
+    /// the playing apps. Clicking one brings up the mixer focused on it.
+    fn show_activity_hud(&mut self, ctx: &egui::Context) {
+        if !self.config.activity_hud {
+            return;
+        }
+        let mut playing: Vec<u32> = self
+            .active_apps
+            .iter()
+            .copied()
+            .filter(|pid| self.apps.get(pid).is_some_and(|props| props.get("Corked").is_none_or(|c| c != "yes")))
+            .collect();
+        playing.sort();
+
+        let rows = playing.len().max(1) as f32;
+        let builder = egui::ViewportBuilder::default()
+            .with_title("Now playing")
+            .with_decorations(false)
+            .with_always_on_top()
+            .with_resizable(false)
+            .with_taskbar(false)
+            .with_inner_size([240.0, 16.0 + 22.0 * rows]);
+
+        let id = egui::ViewportId::from_hash_of("activity_hud");
+        let (clicked, closed) = ctx.show_viewport_immediate(id, builder, |ctx, _class| {
+            let mut clicked = None;
+            egui::CentralPanel::default().show(ctx, |ui| {
+                // No title bar, so let the background move the window.
+                let background = ui.interact(ui.max_rect(), ui.id().with("hud_drag"), egui::Sense::drag());
+                if background.drag_started() {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::StartDrag);
+                }
+                if playing.is_empty() {
+                    ui.weak("Nothing is playing");
+                }
+                for pid in &playing {
+                    let name = pactl::resolve_display_name(&self.apps[pid], &self.config.label_properties);
+                    let text = match self.per_app_volumes.get(pid) {
+                        Some(vol) => format!("🔊 {} · {:.0}%", name, vol),
+                        None => format!("🔊 {}", name),
+                    };
+                    if ui.selectable_label(false, text).on_hover_text("Show in the mixer").clicked() {
+                        clicked = Some(*pid);
+                    }
+                }
+            });
+            (clicked, ctx.input(|i| i.viewport().close_requested()))
+        });
+
+        if closed {
+            self.config.activity_hud = false;
+            self.config.save_later();
+        }
+        if let Some(pid) = clicked {
+            show_main_window(ctx);
+            self.focus = Some(Focus::App(pid));
+            self.scroll_to_focus = true;
+            if self.config.collapsible_app_groups {
+                set_app_group_open(ctx, pid, true);
+            }
+        }
+    }
+
+    /// Shown above the sections while the mixer is locked, with the way out.
+    fn lock_banner(&mut self, ui: &mut egui::Ui) {
+        egui::Frame::group(ui.style()).fill(ui.visuals().faint_bg_color).show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.strong("🔒 Mixer locked");
+                ui.label("Controls are read-only.");
+            });
+            ui.horizontal(|ui| {
+                let unlock = if self.config.lock_pin.is_some() {
+                    let entry = ui.add(
+                        egui::TextEdit::singleline(&mut self.pin_entry)
+                            .password(true)
+                            .hint_text("PIN")
+                            .desired_width(80.0),
+                    );
+                    let enter = entry.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    ui.button("Unlock").clicked() || enter
+                } else {
+                    ui.button("🔓 Unlock").clicked()
+                };
+                if unlock {
+                    if self.config.lock_pin.as_ref().is_none_or(|pin| *pin == self.pin_entry) {
+                        self.locked = false;
+                        self.pin_rejected = false;
+                    } else {
+                        self.pin_rejected = true;
+                    }
+                    self.pin_entry.clear();
+                }
+                if self.pin_rejected {
+                    ui.colored_label(ui.visuals().error_fg_color, "Wrong PIN");
+                }
+            });
+        });
+    }
+
+    /// The tray popup: a borderless, always-on-top window with only the
+    /// system slider and mute, closed again once it loses focus.
+    fn show_popup(&mut self, ctx: &egui::Context) {
+        let Some(pos) = self.popup.as_ref().map(|p| p.pos) else {
+            return;
+        };
+        let builder = egui::ViewportBuilder::default()
+            .with_title("System volume")
+            .with_decorations(false)
+            .with_always_on_top()
+            .with_resizable(false)
+            .with_taskbar(false)
+            .with_position(pos)
+            .with_inner_size(POPUP_SIZE);
+
+        let close = ctx.show_viewport_immediate(egui::ViewportId::from_hash_of("tray_popup"), builder, |ctx, _class| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                if let Some(sink) = self.current_sink() {
+                    ui.strong(self.config.sink_label(&sink.name, &sink.description));
+                }
+                ui.add_enabled_ui(!self.locked, |ui| self.system_controls(ui));
+            });
+
+            let (focused, close_requested, escape) = ctx.input(|i| {
+                (i.viewport().focused, i.viewport().close_requested(), i.key_pressed(egui::Key::Escape))
+            });
+            let popup = self.popup.as_mut().expect("checked above");
+            popup.seen_focus |= focused == Some(true);
+            close_requested || escape || (popup.seen_focus && focused == Some(false))
+        });
+        if close {
+            self.popup = None;
+        }
+    }
+
+    /// Nudge the focused section's volume by `delta` percent.
+    fn adjust_focused(&mut self, delta: f32) {
+        let target = match self.focus {
+            Some(Focus::System) => VolumeTarget::System,
+            Some(Focus::Microphone) => VolumeTarget::Mic,
+            Some(Focus::Recording(index)) => VolumeTarget::Recording(index),
+            Some(Focus::App(pid)) => VolumeTarget::App(pid),
+            None => return,
+        };
+        self.step_volumes(&[target], delta);
+    }
+
+    /// Every relative change (keys, wheel, bindings, media keys) goes through
+    /// here: step each of `targets` by `delta` percent. Nothing is sent for
+    /// one already at the bound it moves towards (see `volume_steps`), so
+    /// holding a key there doesn't spam identical commands.
+    fn step_volumes(&mut self, targets: &[VolumeTarget], delta: f32) {
+        for (target, vol) in volume_steps(targets, delta, |target| self.current_volume(target)) {
+            match target {
+                VolumeTarget::System => self.change_system_volume(vol),
+                VolumeTarget::Mic => {
+                    self.mic_vol = Some(vol);
+                    self.send_volume(target, vol);
+                }
+                VolumeTarget::Recording(index) => {
+                    self.per_recording_volumes.insert(index, vol);
+                    self.send_volume(target, vol);
+                }
+                VolumeTarget::App(pid) => self.change_app_volume(pid, vol),
+            }
+        }
+    }
+
+    /// What `target`'s slider shows, if it has a volume at all.
+    fn current_volume(&self, target: VolumeTarget) -> Option<f32> {
+        match target {
+            VolumeTarget::System => self.has_output.then_some(self.vol_target),
+            VolumeTarget::Mic => self.mic_vol,
+            VolumeTarget::Recording(index) => self.per_recording_volumes.get(&index).copied(),
+            VolumeTarget::App(pid) => self.per_app_volumes.get(&pid).copied(),
+        }
+    }
+
+    /// Whether `focus` is highlighted, and whether it should scroll into view
+    /// because the keyboard focus just moved to it.
+    fn focus_state(&self, focus: Focus) -> (bool, bool) {
+        let focused = self.focus == Some(focus);
+        (focused, focused && self.scroll_to_focus)
+    }
+
+    fn settings_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("⚙ Settings").show(ui, |ui| {
+            if let Some(error) = &self.config.load_error {
+                ui.colored_label(
+                    ui.visuals().error_fg_color,
+                    format!("Config not loaded, changes won't be saved: {}", error),
+                );
+            }
+            let before = self.config.channel_mode;
+            egui::ComboBox::from_label("Multi-channel volume shows")
+                .selected_text(self.config.channel_mode.label())
+                .show_ui(ui, |ui| {
+                    for mode in ChannelMode::ALL {
+                        ui.selectable_value(&mut self.config.channel_mode, mode, mode.label());
+                    }
+                });
+            if self.config.channel_mode != before {
+                self.config.save_later();
+                self.refresh_volumes();
+            }
+            if ui
+                .checkbox(&mut self.config.show_raw_volumes, "Show raw volumes on hover (advanced)")
+                .on_hover_text("Each slider's tooltip lists pactl's per-channel values behind the percentage shown")
+                .changed()
+            {
+                self.config.save_later();
+            }
+
+            if ui
+                .checkbox(&mut self.config.integer_volumes, "Send whole percentages only")
+                .on_hover_text("Round every volume the mixer sets to the nearest percent, like most other mixers")
+                .changed()
+            {
+                pactl::set_integer_volumes(self.config.integer_volumes);
+                self.config.save_later();
+            }
+
+            if ui
+                .checkbox(&mut self.config.confirm_mute_playing, "Ask before muting an app that's playing media")
+                .on_hover_text("Only for streams whose media role is music or video and that aren't paused")
+                .changed()
+            {
+                self.config.save_later();
+            }
+
+            if ui
+                .checkbox(&mut self.config.confirm_overboost, "Ask before boosting apps above 100% from saved volumes")
+                .on_hover_text("Saved volumes and scenes above 100% start at 100% until you confirm them")
+                .changed()
+            {
+                self.config.save_later();
+            }
+
+            if ui
+                .checkbox(&mut self.config.show_effective_volume, "Show effective app levels")
+                .on_hover_text("An app's audible level is its own volume times its output's volume")
+                .changed()
+            {
+                self.config.save_later();
+            }
+
+            if ui
+                .checkbox(&mut self.config.show_loudness_bars, "Show loudness bars")
+                .on_hover_text("A second bar under each app's meter: its live level scaled by its volume")
+                .changed()
+            {
+                self.config.save_later();
+            }
+
+            ui.add_enabled_ui(self.hotkeys.available(), |ui| {
+                if ui
+                    .checkbox(&mut self.config.global_media_keys, "Volume media keys work when the mixer isn't focused")
+                    .on_disabled_hover_text("Global hotkeys aren't available (X11 only)")
+                    .changed()
+                {
+                    self.config.save_later();
+                }
+            });
+
+            self.panic_key_settings(ui);
+
+            ui.add_enabled_ui(self.tray.available(), |ui| {
+                if ui
+                    .checkbox(&mut self.config.tray_popup, "Clicking the tray icon opens a compact volume popup")
+                    .on_hover_text("Otherwise it brings up this window; the full window is always in the tray menu")
+                    .on_disabled_hover_text("No system tray (StatusNotifierItem) found")
+                    .changed()
+                {
+                    self.config.save_later();
+                }
+                if ui
+                    .checkbox(&mut self.config.start_hidden, "Start hidden in the tray")
+                    .on_hover_text("For autostart: the window stays closed until opened from the tray icon")
+                    .on_disabled_hover_text("No system tray (StatusNotifierItem) found")
+                    .changed()
+                {
+                    self.config.save_later();
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("When the last app closes:");
+                let before = self.config.when_no_apps;
+                egui::ComboBox::from_id_source("when_no_apps")
+                    .selected_text(before.label())
+                    .show_ui(ui, |ui| {
+                        for action in NoAppsAction::ALL {
+                            let enabled = action != NoAppsAction::HideToTray || self.tray.available();
+                            let option = egui::SelectableLabel::new(before == action, action.label());
+                            let clicked = ui
+                                .add_enabled(enabled, option)
+                                .on_disabled_hover_text("No system tray (StatusNotifierItem) found")
+                                .clicked();
+                            if clicked {
+                                self.config.when_no_apps = action;
+                            }
+                        }
+                    });
+                if self.config.when_no_apps != before {
+                    self.config.save_later();
+                }
+            });
+
+            if ui
+                .checkbox(&mut self.config.show_monitor_level, "Show the output's monitor level")
+                .on_hover_text("A meter of what plays on the default output, measured on its monitor source")
+                .changed()
+            {
+                self.sync_monitor_meter();
+                self.config.save_later();
+            }
+
+            self.accent_settings(ui);
+
+            if ui
+                .checkbox(&mut self.config.activity_hud, "Floating \"now playing\" window")
+                .on_hover_text("A small always-on-top list of the apps making sound; click one to jump to it")
+                .changed()
+            {
+                self.config.save_later();
+            }
+
+            let mut compact = self.config.compact_apps;
+            if ui
+                .checkbox(&mut compact, "Compact app list (F2)")
+                .on_hover_text("One read-only row per app: volume, mute state and meter. Click one for its controls")
+                .changed()
+            {
+                self.toggle_compact_apps();
+            }
+            if ui
+                .checkbox(&mut self.config.show_window_titles, "Show window titles")
+                .on_hover_text("Each app's window title, updated every few seconds. Needs wmctrl and xprop (X11)")
+                .changed()
+            {
+                self.window_titles.set_enabled(self.config.show_window_titles);
+                self.config.save_later();
+            }
+            if ui
+                .checkbox(&mut self.config.group_apps_by_output, "Group apps by output")
+                .on_hover_text("With apps on several outputs, list them under a header per output")
+                .changed()
+            {
+                self.config.save_later();
+            }
+            if ui.checkbox(&mut self.config.collapsible_app_groups, "Collapsible app groups").changed() {
+                self.config.save_later();
+            }
+            ui.indent("app_group_settings", |ui| {
+                ui.add_enabled_ui(self.config.collapsible_app_groups, |ui| {
+                    let expand = ui
+                        .checkbox(&mut self.config.auto_expand_active, "Expand an app when it starts making sound")
+                        .changed();
+                    let collapse = ui
+                        .add_enabled(
+                            self.config.auto_expand_active,
+                            egui::Checkbox::new(&mut self.config.auto_collapse_idle, "Collapse it again once it's quiet"),
+                        )
+                        .on_hover_text("After about 10 s without sound")
+                        .changed();
+                    if expand || collapse {
+                        self.config.save_later();
+                    }
+                });
+            });
+
+            self.label_settings(ui);
+
+            ui.label("Advanced:");
+            if ui
+                .checkbox(&mut self.config.show_other_sessions, "Show streams of other users and system-wide servers")
+                .on_hover_text(
+                    "Tries the system-wide server and other users' sockets under /run/user. \
+                     Most are refused unless an administrator allowed access; they are shown read-only.",
+                )
+                .changed()
+            {
+                self.show_other_sessions.store(self.config.show_other_sessions, Ordering::Relaxed);
+                self.config.save_later();
+            }
+            self.control_settings(ui);
+            self.quantum_settings(ui);
+            self.hook_settings(ui);
+            self.keybinding_settings(ui);
+            self.auto_mute_settings(ui);
+            self.lock_settings(ui);
+
+            ui.horizontal(|ui| {
+                ui.label("Write settings at most every");
+                let interval = ui.add(
+                    egui::DragValue::new(&mut self.config.save_interval_secs)
+                        .clamp_range(0.0..=60.0)
+                        .speed(0.1)
+                        .suffix(" s"),
+                );
+                if interval.on_hover_text("Changes are also written when the window loses focus and on exit").changed() {
+                    self.config.save_later();
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Leave between sound server commands");
+                let interval = ui.add(
+                    egui::DragValue::new(&mut self.config.min_command_interval_ms)
+                        .clamp_range(0..=1000)
+                        .speed(5.0)
+                        .suffix(" ms"),
+                );
+                if interval
+                    .on_hover_text(
+                        "Spaces out every pactl/wpctl/pw-metadata process, for slow machines; while dragging only \
+                         the latest volume is sent, and the sound follows a bit later",
+                    )
+                    .changed()
+                {
+                    exec::set_min_interval(Duration::from_millis(self.config.min_command_interval_ms.into()));
+                    self.config.save_later();
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Cancel unanswered confirmations after");
+                let timeout = ui.add(
+                    egui::DragValue::new(&mut self.config.confirm_timeout_secs)
+                        .clamp_range(0.0..=300.0)
+                        .speed(0.5)
+                        .suffix(" s"),
+                );
+                if timeout.on_hover_text("For deleting, resetting and applying scenes; 0 waits forever").changed() {
+                    self.config.save_later();
+                }
+            });
+
+            let mut offer = !self.config.suppress_remember_prompt;
+            if ui.checkbox(&mut offer, "Offer to remember app volumes for new streams").changed() {
+                self.config.suppress_remember_prompt = !offer;
+                self.config.save_later();
+            }
+        });
+    }
+
+    fn scenes_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("🎬 Scenes").show(ui, |ui| {
+            let mut apply = None;
+            let mut delete = None;
+            let timeout = self.config.confirm_timeout_secs;
+            for name in self.config.scenes.keys() {
+                ui.horizontal(|ui| {
+                    ui.label(name);
+                    if ui.button("Apply").clicked() {
+                        apply = Some(name.clone());
+                    }
+                    let id = egui::Id::new(("delete_scene", name));
+                    if confirm_button(ui, &mut self.confirming, timeout, id, |ui| {
+                        ui.small_button("🗑").on_hover_text("Delete this scene")
+                    }) {
+                        delete = Some(name.clone());
+                    }
+                });
+            }
+            if let Some(name) = apply
+                && let Some(scene) = self.config.scenes.get(&name)
+            {
+                let (steps, missing) = self.scene_plan(scene);
+                let since = std::time::Instant::now();
+                self.scene_preview = Some(ScenePreview { name, steps, missing, since });
+            }
+            if let Some(name) = delete {
+                self.config.scenes.remove(&name);
+                self.config.save_later();
+            }
+            self.scene_preview_panel(ui);
+
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.scene_name_edit).hint_text("Scene name, e.g. Gaming"));
+                let name = self.scene_name_edit.trim().to_string();
+                let exists = self.config.scenes.contains_key(&name);
+                let save = ui
+                    .add_enabled(!name.is_empty(), egui::Button::new(if exists { "Overwrite" } else { "Save current" }))
+                    .on_hover_text("Remember the default output, card profiles and each app's output, volume and mute");
+                if save.clicked() {
+                    let scene = self.capture_scene();
+                    self.config.scenes.insert(name, scene);
+                    self.config.save_later();
+                    self.scene_name_edit.clear();
+                }
+            });
+        });
+    }
+
+    fn capture_scene(&self) -> Scene {
+        let mut apps = BTreeMap::new();
+        for (pid, props) in &self.apps {
+            let Some(app) = pactl::app_key(props) else {
+                continue;
+            };
+            let sink = self
+                .stream_sink(props)
+                .filter(|s| !s.name.starts_with(pactl::OWN_SINK_PREFIX))
+                .map(|s| s.name.clone());
+            apps.insert(
+                app.to_string(),
+                SceneApp {
+                    sink,
+                    volume: self.per_app_volumes.get(pid).copied(),
+                    muted: self.per_app_muted.get(pid).copied().unwrap_or(false),
+                },
+            );
+        }
+        Scene {
+            default_sink: self.default_sink.clone(),
+            cards: self.card_profiles.clone(),
+            apps,
+        }
+    }
+
+    /// What applying `scene` would change, in order: card profiles first so
+    /// their sinks exist, then the default output, then every running app the
+    /// scene knows about. Also returns the scene's apps that aren't running.
+    fn scene_plan(&self, scene: &Scene) -> (Vec<SceneStep>, Vec<String>) {
+        let mut steps = Vec::new();
+        for (card, profile) in &scene.cards {
+            if self.card_profiles.get(card).is_some_and(|p| p != profile) {
+                steps.push(SceneStep::CardProfile { card: card.clone(), profile: profile.clone() });
+            }
+        }
+        if let Some(sink) = &scene.default_sink
+            && self.default_sink.as_ref() != Some(sink)
+        {
+            steps.push(SceneStep::DefaultSink(sink.clone()));
+        }
+
+        let mut pids: Vec<u32> = self.apps.keys().copied().collect();
+        pids.sort();
+        for pid in pids {
+            let props = &self.apps[&pid];
+            let Some(app) = pactl::app_key(props).and_then(|key| scene.apps.get(key)) else {
+                continue;
+            };
+            if let Some(sink) = &app.sink
+                && self.stream_sink(props).is_none_or(|s| &s.name != sink)
+            {
+                steps.push(SceneStep::Move { pid, sink: sink.clone() });
+            }
+            if let Some(volume) = app.volume
+                && self.per_app_volumes.get(&pid).is_none_or(|v| (v - volume).abs() >= 0.5)
+            {
+                steps.push(SceneStep::Volume { pid, volume });
+            }
+            if self.per_app_muted.get(&pid).copied().unwrap_or(false) != app.muted {
+                steps.push(SceneStep::Mute { pid, muted: app.muted });
+            }
+        }
+
+        let missing = scene
+            .apps
+            .keys()
+            .filter(|app| !self.apps.values().any(|props| pactl::app_key(props) == Some(app.as_str())))
+            .cloned()
+            .collect();
+        (steps, missing)
+    }
+
+    /// Carry out a scene's steps. Streams that closed since the preview are
+    /// skipped.
+    fn apply_scene_steps(&mut self, steps: Vec<SceneStep>) {
+        for step in steps {
+            if let SceneStep::Move { pid, .. } | SceneStep::Volume { pid, .. } | SceneStep::Mute { pid, .. } = step
+                && !self.apps.contains_key(&pid)
+            {
+                continue;
+            }
+            match step {
+                SceneStep::CardProfile { card, profile } => {
+                    self.post(None, move || pactl::set_card_profile(&card, &profile));
+                }
+                SceneStep::DefaultSink(sink) => self.set_default_sink(sink),
+                SceneStep::Move { pid, sink } => {
+                    self.post(None, move || pactl::move_sink_input(pid, &sink));
+                }
+                SceneStep::Volume { pid, volume } => {
+                    let volume = self.hold_boost(pid, volume);
+                    self.change_app_volume(pid, volume);
+                }
+                SceneStep::Mute { pid, muted } => self.change_app_mute(pid, muted),
+            }
+        }
+    }
+
+    /// The volume to set now for a saved or scene `volume`: above 100% it is
+    /// held at 100% until `boost_panel` confirms it (if so configured).
+    fn hold_boost(&mut self, pid: u32, volume: f32) -> f32 {
+        if volume > 100.0 && self.config.confirm_overboost {
+            self.pending_boosts.insert(pid, volume);
+            100.0
+        } else {
+            self.pending_boosts.remove(&pid);
+            volume
+        }
+    }
+
+    /// Saved volumes above 100% held back by `hold_boost`, listed with
+    /// Apply / Keep at 100%.
+    fn boost_panel(&mut self, ui: &mut egui::Ui) {
+        if self.pending_boosts.is_empty() {
+            return;
+        }
+        let mut decision = None;
+        egui::Frame::group(ui.style()).show(ui, |ui| {
+            ui.strong("⚠ Saved volumes above 100%, held at 100%:");
+            for (pid, volume) in &self.pending_boosts {
+                let name = self.apps.get(pid).map_or("Unknown", |props| {
+                    pactl::resolve_display_name(props, &self.config.label_properties)
+                });
+                ui.label(format!("• {} (pid: {}): {:.0}%", name, pid, volume));
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Apply boost").clicked() {
+                    decision = Some(true);
+                }
+                if ui.button("Keep at 100%").clicked() {
+                    decision = Some(false);
+                }
+            });
+        });
+        match decision {
+            Some(true) => {
+                for (pid, volume) in std::mem::take(&mut self.pending_boosts) {
+                    self.change_app_volume(pid, volume);
+                }
+            }
+            Some(false) => self.pending_boosts.clear(),
+            None => {}
+        }
+    }
+
+    /// The changes a scene is about to make, with Apply / Cancel. Apply
+    /// carries out exactly the listed changes.
+    fn scene_preview_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(preview) = &self.scene_preview else {
+            return;
+        };
+        let timeout = self.config.confirm_timeout_secs;
+        let left = timeout - preview.since.elapsed().as_secs_f32();
+        if timeout > 0.0 && left <= 0.0 {
+            self.scene_preview = None;
+            return;
+        }
+
+        let mut decision = None;
+        egui::Frame::group(ui.style()).show(ui, |ui| {
+            ui.strong(format!("Apply \"{}\"?", preview.name));
+            if preview.steps.is_empty() {
+                ui.weak("Nothing would change: everything already matches.");
+            }
+            for step in &preview.steps {
+                ui.label(format!("• {}", self.describe_scene_step(step)));
+            }
+            if !preview.missing.is_empty() {
+                ui.weak(format!("Not running, skipped: {}", preview.missing.join(", ")));
+            }
+            ui.horizontal(|ui| {
+                if ui.add_enabled(!preview.steps.is_empty(), egui::Button::new("Apply")).clicked() {
+                    decision = Some(true);
+                }
+                if ui.button("Cancel").clicked() {
+                    decision = Some(false);
+                }
+                if timeout > 0.0 {
+                    ui.weak(format!("Cancels in {:.0} s", left.ceil()));
+                    ui.ctx().request_repaint_after(Duration::from_millis(250));
+                }
+            });
+        });
+        match decision {
+            Some(true) => {
+                if let Some(preview) = self.scene_preview.take() {
+                    self.apply_scene_steps(preview.steps);
+                }
+            }
+            Some(false) => self.scene_preview = None,
+            None => {}
+        }
+    }
+
+    fn describe_scene_step(&self, step: &SceneStep) -> String {
+        let app = |pid: &u32| {
+            let name = self.apps.get(pid).and_then(pactl::app_key).unwrap_or("Unknown");
+            format!("{} (pid: {})", name, pid)
+        };
+        let sink = |name: &str| match self.sinks.iter().find(|s| s.name == name) {
+            Some(s) => self.config.sink_label(&s.name, &s.description).to_string(),
+            None => format!("{} (not present)", name),
+        };
+        match step {
+            SceneStep::CardProfile { card, profile } => format!("Card {}: profile {}", card, profile),
+            SceneStep::DefaultSink(name) => format!("Default output: {}", sink(name)),
+            SceneStep::Move { pid, sink: name } => format!("{}: play on {}", app(pid), sink(name)),
+            SceneStep::Volume { pid, volume } => match self.per_app_volumes.get(pid) {
+                Some(from) => format!("{}: volume {:.0}% → {:.0}%", app(pid), from, volume),
+                None => format!("{}: volume {:.0}%", app(pid), volume),
+            },
+            SceneStep::Mute { pid, muted: true } => format!("{}: mute", app(pid)),
+            SceneStep::Mute { pid, muted: false } => format!("{}: unmute", app(pid)),
+        }
+    }
+
+    fn control_settings(&mut self, ui: &mut egui::Ui) {
+        let mut enabled = self.config.control_socket;
+        if ui
+            .checkbox(&mut enabled, "Control socket for scripts")
+            .on_hover_text("Line commands such as `set-system 60` or `mute-app firefox` on a socket in $XDG_RUNTIME_DIR")
+            .changed()
+        {
+            self.config.control_socket = enabled;
+            self.config.save_later();
+            self.control = match std::mem::replace(&mut self.control, Ok(None)) {
+                Ok(Some(control)) if !enabled => {
+                    control.stop();
+                    Ok(None)
+                }
+                Ok(None) | Err(_) if enabled => Control::start(ui.ctx()).map(Some),
+                other => other,
+            };
+        }
+        match &self.control {
+            Ok(Some(control)) => {
+                ui.weak(format!("Listening on {}", control.path().display()));
+            }
+            Err(e) if enabled => {
+                ui.colored_label(ui.visuals().error_fg_color, format!("Control socket not started: {}", e));
+            }
+            _ => {}
+        }
+    }
+
+    /// Have the command worker (re-)read the clock settings into
+    /// `clock_settings`. Jobs run in order, so one posted after a change reads
+    /// its outcome.
+    fn read_clock_settings(&mut self) {
+        self.clock_requested = true;
+        let slot = Arc::clone(&self.clock_settings);
+        let ctx = self.ctx.clone();
+        exec::post(None, move || {
+            *slot.lock().unwrap_or_else(|e| e.into_inner()) = Some(pactl::clock_settings());
+            ctx.request_repaint();
+        });
+    }
+
+    /// The graph-wide quantum (PipeWire's `clock.force-quantum`).
+    fn quantum_settings(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Global latency (PipeWire quantum)").show(ui, |ui| {
+            if !self.clock_requested {
+                self.read_clock_settings();
+            }
+            let read = self.clock_settings.lock().unwrap_or_else(|e| e.into_inner()).clone();
+            let clock = match read {
+                Some(Ok(clock)) => clock,
+                Some(Err(VolumeError::BinaryNotFound(_))) => {
+                    ui.weak("Not available: needs PipeWire and pw-metadata.");
+                    return;
+                }
+                Some(Err(e)) => {
+                    ui.weak(format!("Not available: {}", e));
+                    return;
+                }
+                None => {
+                    ui.weak("Reading…");
+                    return;
+                }
+            };
+            let rate = clock.rate.unwrap_or(48000);
+            let ms = |frames: u32| frames as f32 * 1000.0 / rate as f32;
+            let current = match (clock.forced_quantum, clock.quantum) {
+                (Some(forced), _) => format!("Forced: {} frames ({:.1} ms)", forced, ms(forced)),
+                (None, Some(quantum)) => {
+                    format!("Default: {} frames ({:.1} ms), streams may ask for others", quantum, ms(quantum))
+                }
+                (None, None) => "Not reported".to_string(),
+            };
+            ui.label(current);
+            let mut set = None;
+            ui.horizontal(|ui| {
+                for preset in QUANTUM_PRESETS {
+                    let label = format!("{} ({:.0} ms)", preset, ms(preset));
+                    if ui.selectable_label(clock.forced_quantum == Some(preset), label).clicked() {
+                        set = Some(Some(preset));
+                    }
+                }
+                if ui.add_enabled(clock.forced_quantum.is_some(), egui::Button::new("Don't force")).clicked() {
+                    set = Some(None);
+                }
+            });
+            if let Some(quantum) = set {
+                self.post(None, move || pactl::force_quantum(quantum));
+                self.read_clock_settings(); // read it back, once it is set
+            }
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "Advanced: smaller values lower latency for everything but may cause crackles and dropouts. \
+                 The setting lasts until PipeWire restarts.",
+            );
+        });
+    }
+
+    fn accent_settings(&mut self, ui: &mut egui::Ui) {
+        let found = self.system_accent.get().is_some();
+        let checkbox = egui::Checkbox::new(&mut self.config.follow_system_accent, "Use the desktop's accent color");
+        let follow = ui
+            .add_enabled(found, checkbox)
+            .on_disabled_hover_text("The desktop doesn't report one (XDG settings portal)")
+            .changed();
+        ui.horizontal(|ui| {
+            let mut custom = self.config.accent_color.is_some();
+            let toggled = ui.checkbox(&mut custom, "Custom accent color").changed();
+            let mut color = self.config.accent_color.or(self.applied_accent).unwrap_or([0, 92, 128]);
+            let picked = ui.add_enabled_ui(custom, |ui| ui.color_edit_button_srgb(&mut color).changed()).inner;
+            if toggled || picked || follow {
+                self.config.accent_color = custom.then_some(color);
+                self.config.save_later();
+            }
+        });
+    }
+
+    fn hook_settings(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Event hook:").on_hover_text(
+                "Run on new streams, mutes and threshold crossings, with $K_VOLUME_MIXER_EVENT, _TARGET, _NAME \
+                 and _VALUE set",
+            );
+            let edit = egui::TextEdit::singleline(&mut self.hook_command_edit).hint_text("shell command");
+            ui.add(edit.desired_width(200.0));
+            let command = self.hook_command_edit.trim().to_string();
+            let changed = !command.is_empty() && self.config.hook_command.as_ref() != Some(&command);
+            if ui.add_enabled(changed, egui::Button::new("Set")).clicked() {
+                self.config.hook_command = Some(command);
+                self.config.save_later();
+            }
+            if ui.add_enabled(self.config.hook_command.is_some(), egui::Button::new("Clear")).clicked() {
+                self.config.hook_command = None;
+                self.hook_command_edit.clear();
+                self.hooks.reset();
+                self.config.save_later();
+            }
+        });
+        ui.add_enabled_ui(self.config.hook_command.is_some(), |ui| {
+            ui.horizontal(|ui| {
+                let mut enabled = self.config.hook_volume_threshold.is_some();
+                let toggled = ui.checkbox(&mut enabled, "Also when a volume crosses").changed();
+                let mut threshold = self.config.hook_volume_threshold.unwrap_or(100.0);
+                let dragged = ui
+                    .add_enabled(enabled, egui::DragValue::new(&mut threshold).clamp_range(0.0..=150.0).suffix("%"))
+                    .changed();
+                if toggled || dragged {
+                    self.config.hook_volume_threshold = enabled.then_some(threshold);
+                    self.config.save_later();
+                }
+            });
+        });
+        if self.hooks.dropped() > 0 {
+            ui.weak(format!("{} events skipped: the hook ran too often", self.hooks.dropped()));
+        }
+    }
+
+    fn panic_key_settings(&mut self, ui: &mut egui::Ui) {
+        ui.add_enabled_ui(self.hotkeys.available(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Panic key:")
+                    .on_hover_text("Mutes the system and every app from anywhere; press it again to restore them")
+                    .on_disabled_hover_text("Global hotkeys aren't available (X11 only)");
+                let edit = egui::TextEdit::singleline(&mut self.panic_key_edit).hint_text("e.g. Ctrl+Alt+M");
+                ui.add(edit.desired_width(120.0));
+                let combo = self.panic_key_edit.trim().to_string();
+                let changed = !combo.is_empty() && self.config.panic_key.as_ref() != Some(&combo);
+                if ui.add_enabled(changed, egui::Button::new("Set")).clicked() {
+                    self.panic_key_error = self.hotkeys.set_panic_key(Some(&combo)).err();
+                    if self.panic_key_error.is_none() {
+                        self.config.panic_key = Some(combo);
+                        self.config.save_later();
+                    }
+                }
+                if ui.add_enabled(self.config.panic_key.is_some(), egui::Button::new("Clear")).clicked() {
+                    self.panic_key_error = self.hotkeys.set_panic_key(None).err();
+                    self.config.panic_key = None;
+                    self.panic_key_edit.clear();
+                    self.config.save_later();
+                }
+            });
+        });
+        match (&self.panic_key_error, &self.config.panic_key) {
+            (Some(e), _) => {
+                ui.colored_label(ui.visuals().error_fg_color, format!("Panic key not set: {}", e));
+            }
+            (None, Some(combo)) if self.hotkeys.panicked() => {
+                let text = format!("Everything muted by {}; press it again to restore", combo);
+                ui.colored_label(ui.visuals().warn_fg_color, text);
+            }
+            (None, Some(combo)) => {
+                ui.weak(format!("Current: {}", combo));
+            }
+            (None, None) => {}
+        }
+    }
+
+    fn lock_settings(&mut self, ui: &mut egui::Ui) {
+        if ui
+            .checkbox(&mut self.config.lock_on_start, "Start locked")
+            .on_hover_text("For shared machines: the mixer opens read-only until unlocked")
+            .changed()
+        {
+            self.config.save_later();
+        }
+        ui.horizontal(|ui| {
+            ui.label("Unlock PIN:");
+            ui.add(egui::TextEdit::singleline(&mut self.new_pin_edit).password(true).desired_width(80.0));
+            let pin = self.new_pin_edit.trim().to_string();
+            if ui.add_enabled(!pin.is_empty(), egui::Button::new("Set")).clicked() {
+                self.config.lock_pin = Some(pin);
+                self.config.save_later();
+                self.new_pin_edit.clear();
+            }
+            if ui.add_enabled(self.config.lock_pin.is_some(), egui::Button::new("Clear")).clicked() {
+                self.config.lock_pin = None;
+                self.config.save_later();
+            }
+        });
+        ui.weak(if self.config.lock_pin.is_some() {
+            "A PIN is set. It only prevents accidents: it is stored unencrypted in the config file."
+        } else {
+            "No PIN: anyone can unlock."
+        });
+    }
+
+    /// Read-only list of the config's keybindings, with a reload button since
+    /// they're edited in the file.
+    fn keybinding_settings(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Keybindings").show(ui, |ui| {
+            for binding in &self.keybindings {
+                let target = match &binding.action {
+                    BindingAction::ToggleMuteSystem => "Toggle system mute".to_string(),
+                    BindingAction::RaiseSystem => "Raise system volume".to_string(),
+                    BindingAction::LowerSystem => "Lower system volume".to_string(),
+                    BindingAction::ToggleMuteApp(app) => format!("Toggle mute of {}", app),
+                    BindingAction::RaiseApp(app) => format!("Raise {}", app),
+                    BindingAction::LowerApp(app) => format!("Lower {}", app),
+                };
+                ui.horizontal(|ui| {
+                    ui.monospace(binding.shortcut.format(&egui::ModifierNames::NAMES, false));
+                    ui.label(target);
+                });
+            }
+            if self.keybindings.is_empty() && self.keybinding_errors.is_empty() {
+                ui.weak("None yet. Add [[keybindings]] tables to the config file.");
+            }
+            for error in &self.keybinding_errors {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            if ui.button("⟳ Reload from config").clicked() {
+                self.config.keybindings = Config::load().keybindings;
+                (self.keybindings, self.keybinding_errors) = keybindings::parse(&self.config.keybindings);
+            }
+        });
+    }
+
+    /// "Mute X while Y plays" rules: list, remove, add.
+    fn auto_mute_settings(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Auto-mute").show(ui, |ui| {
+            let mut remove = None;
+            for (i, rule) in self.config.auto_mute.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Mute {} while {} plays", rule.mute, rule.when));
+                    if ui.small_button("🗑").on_hover_text("Remove this rule").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                self.config.auto_mute.remove(i);
+                self.config.save_later();
+            }
+            ui.horizontal(|ui| {
+                let (mute, when) = &mut self.auto_mute_edit;
+                ui.label("Mute");
+                ui.add(egui::TextEdit::singleline(mute).hint_text("Spotify").desired_width(100.0));
+                ui.label("while");
+                ui.add(egui::TextEdit::singleline(when).hint_text("Discord").desired_width(100.0));
+                ui.label("plays");
+                let (mute_name, when_name) = (mute.trim(), when.trim());
+                let valid =
+                    !mute_name.is_empty() && !when_name.is_empty() && !mute_name.eq_ignore_ascii_case(when_name);
+                if ui.add_enabled(valid, egui::Button::new("Add")).clicked() {
+                    self.config.auto_mute.push(AutoMuteRule {
+                        mute: mute_name.to_string(),
+                        when: when_name.to_string(),
+                    });
+                    self.config.save_later();
+                    mute.clear();
+                    when.clear();
+                }
+            });
+            ui.weak("Apps match by name or binary. The app is unmuted again once the other one stops or pauses.");
+        });
+    }
+
+    /// The property order used to label streams: reorder, remove, add, reset.
+    fn label_settings(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Label streams by").show(ui, |ui| {
+            let mut changed = false;
+            let mut swap = None;
+            let mut remove = None;
+            let count = self.config.label_properties.len();
+            for (i, key) in self.config.label_properties.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.monospace(format!("{}. {}", i + 1, key));
+                    if ui.add_enabled(i > 0, egui::Button::new("⬆").small()).clicked() {
+                        swap = Some((i, i - 1));
+                    }
+                    if ui.add_enabled(i + 1 < count, egui::Button::new("⬇").small()).clicked() {
+                        swap = Some((i, i + 1));
+                    }
+                    if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some((i, j)) = swap {
+                self.config.label_properties.swap(i, j);
+                changed = true;
+            }
+            if let Some(i) = remove {
+                self.config.label_properties.remove(i);
+                changed = true;
+            }
+
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.label_property_edit).hint_text("e.g. application.name"));
+                let key = self.label_property_edit.trim().to_string();
+                let valid = !key.is_empty() && !self.config.label_properties.contains(&key);
+                if ui.add_enabled(valid, egui::Button::new("Add")).clicked() {
+                    self.config.label_properties.push(key);
+                    self.label_property_edit.clear();
+                    changed = true;
+                }
+                let timeout = self.config.confirm_timeout_secs;
+                if confirm_button(ui, &mut self.confirming, timeout, egui::Id::new("reset_labels"), |ui| {
+                    ui.button("Reset").on_hover_text("Back to the default order")
+                }) {
+                    self.config.label_properties = pactl::DEFAULT_LABEL_PROPERTIES.map(String::from).to_vec();
+                    changed = true;
+                }
+            });
+            ui.weak("The first property a stream has is used; \"Unknown\" if it has none.");
+
+            if changed {
+                self.config.save_later();
+            }
+        });
+    }
+
+    fn system_section(&mut self, ui: &mut egui::Ui) {
+        let (focused, scroll) = self.focus_state(Focus::System);
+        section(ui, focused, scroll, |ui| {
+            if !self.has_output {
+                no_output_banner(ui);
+            }
+            ui.horizontal(|ui| {
+                ui.label("🔊 System Volume:");
+                if let Some(sink) = self.current_sink() {
+                    ui.strong(self.config.sink_label(&sink.name, &sink.description));
+                    self.battery_label(ui, &sink.name);
+                }
+            });
+            self.system_controls(ui);
+            if let Some(source) = self.monitor_meter.source() {
+                ui.horizontal(|ui| {
+                    ui.weak("Monitor");
+                    ui.add(egui::ProgressBar::new(self.monitor_level).desired_height(6.0))
+                        .on_hover_text("Everything playing on this output, after its volume");
+                    if ui
+                        .small_button("📋")
+                        .on_hover_text(format!(
+                            "Copy \"{}\": record from this source to capture desktop audio",
+                            source
+                        ))
+                        .clicked()
+                    {
+                        ui.output_mut(|o| o.copied_text = source.to_string());
+                    }
+                });
+            }
+            if !self.sinks.is_empty() {
+                self.device_picker(ui);
+            }
+        });
+    }
+
+    /// System volume slider and mute toggle, shared with the tray popup.
+    fn system_controls(&mut self, ui: &mut egui::Ui) {
+        let mut vol = self.vol;
+        let raw = self
+            .current_sink()
+            .filter(|_| self.config.show_raw_volumes)
+            .map(|sink| raw_volume_text(&format!("{:.0}% (from wpctl)", self.vol_target), &sink.volume));
+        ui.horizontal(|ui| {
+            let slider = egui::Slider::new(&mut vol, 0.0..=100.0).text("%");
+            let mut slider = ui.add_enabled(self.has_output, slider);
+            if let Some(raw) = raw {
+                slider = slider.on_hover_text(raw);
+            }
+            if slider.changed() {
+                self.change_system_volume(vol);
+            }
+            let muted = self.sys_muted;
+            let mute = ui
+                .add_enabled(self.has_output, egui::SelectableLabel::new(muted, if muted { "🔇" } else { "🔊" }))
+                .on_hover_text(if muted { "Unmute" } else { "Mute" });
+            if mute.clicked() {
+                self.change_system_mute(!muted);
+            }
+        });
+    }
+
+    fn current_sink(&self) -> Option<&Sink> {
+        let default = self.default_sink.as_deref()?;
+        self.sinks.iter().find(|s| s.name == default)
+    }
+
+    /// Output device dropdown plus an inline editor for the selected device's
+    /// friendly name.
+    fn device_picker(&mut self, ui: &mut egui::Ui) {
+        if let Some((name, buffer)) = &mut self.renaming {
+            let mut done = None;
+            ui.horizontal(|ui| {
+                let edit = ui.text_edit_singleline(buffer);
+                let entered = edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if entered || ui.button("Save").clicked() {
+                    done = Some(true);
+                }
+                if ui.button("Cancel").clicked() {
+                    done = Some(false);
+                }
+            });
+            match done {
+                Some(true) => {
+                    let friendly = buffer.trim();
+                    if friendly.is_empty() {
+                        self.config.sink_names.remove(name.as_str());
+                    } else {
+                        self.config.sink_names.insert(name.clone(), friendly.to_string());
+                    }
+                    self.config.save_later();
+                    self.renaming = None;
+                }
+                Some(false) => self.renaming = None,
+                None => {}
+            }
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            let current = self.current_sink().cloned();
+            let selected_text = current
+                .as_ref()
+                .map(|s| self.config.sink_label(&s.name, &s.description))
+                .unwrap_or("None");
+            let mut selected = current.as_ref().map(|s| s.name.clone());
+            egui::ComboBox::from_id_source("output_device")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    for sink in &self.sinks {
+                        let label = self.config.sink_label(&sink.name, &sink.description);
+                        ui.selectable_value(&mut selected, Some(sink.name.clone()), label)
+                            .on_hover_text(&sink.name);
+                    }
+                });
+            if let Some(name) = selected
+                && current.as_ref().is_none_or(|c| c.name != name)
+            {
+                self.set_default_sink(name);
+            }
+
+            if ui.small_button("⟳").on_hover_text("Re-read the device lists").clicked() {
+                self.device_watch.refresh();
+            }
+
+            if let Some(sink) = current {
+                let button = ui.small_button("✏").on_hover_text("Rename this device (leave empty to reset)");
+                if button.clicked() {
+                    let friendly = self.config.sink_names.get(&sink.name).cloned().unwrap_or_default();
+                    self.renaming = Some((sink.name, friendly));
+                }
+            }
+        });
+    }
+
+    fn microphone_section(&mut self, ui: &mut egui::Ui) {
+        let Some(mut vol) = self.mic_vol else {
+            return;
+        };
+        let (focused, scroll) = self.focus_state(Focus::Microphone);
+        let muted = self.mic_muted;
+        let (changed, mute) = section(ui, focused, scroll, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("🎤 Microphone:");
+                if let Some(name) = &self.mic_name {
+                    ui.strong(name);
+                }
+            });
+            ui.horizontal(|ui| {
+                let changed = ui.add(egui::Slider::new(&mut vol, 0.0..=100.0).text("%")).changed();
+                let mute = ui
+                    .selectable_label(muted, if muted { "🔇" } else { "🎤" })
+                    .on_hover_text(if muted { "Unmute the microphone" } else { "Mute the microphone" });
+                (changed, mute.clicked())
+            })
+            .inner
+        });
+        if changed {
+            self.mic_vol = Some(vol);
+            self.send_volume(VolumeTarget::Mic, vol);
+        }
+        if mute {
+            self.change_mic_mute(!muted);
+        }
+
+        let mut remember = self.config.mic_muted.is_some();
+        if ui
+            .checkbox(&mut remember, "Keep this mute state after restarting")
+            .on_hover_text("Mute (or unmute) the microphone like this every time the mixer starts")
+            .changed()
+        {
+            self.config.mic_muted = remember.then_some(self.mic_muted);
+            self.config.save_later();
+        }
+    }
+
+    /// One row per output device with bulk mute/restore for the streams
+    /// routed to it. Only worth showing when there's more than one device.
+    fn outputs_section(&mut self, ui: &mut egui::Ui) {
+        if self.sinks.len() < 2 {
+            return;
+        }
+
+        ui.label("🔈 Outputs:");
+        for sink in self.sinks.clone() {
+            let streams = self.streams_on_sink(sink.index);
+            ui.horizontal(|ui| {
+                ui.label(self.config.sink_label(&sink.name, &sink.description));
+                self.battery_label(ui, &sink.name);
+                ui.weak(format!("{} stream(s)", streams.len()));
+                if self.sink_mutes.contains_key(&sink.index) {
+                    if ui.small_button("Restore streams").clicked() {
+                        self.restore_sink_streams(sink.index);
+                    }
+                } else if ui
+                    .add_enabled(!streams.is_empty(), egui::Button::new("Mute streams").small())
+                    .on_hover_text("Mute every app playing here (the device itself stays unmuted)")
+                    .clicked()
+                {
+                    self.mute_sink_streams(sink.index);
+                }
+                let moving = self.route_move.is_some();
+                if ui
+                    .add_enabled(!moving, egui::Button::new("⇶ Send everything here").small())
+                    .on_hover_text("Make this the default output and move every app onto it")
+                    .clicked()
+                {
+                    self.send_all_to(&sink.name);
+                }
+                if !moving
+                    && self.route_undo.as_ref().is_some_and(|u| u.sink == sink.name)
+                    && ui.small_button("↶ Undo").on_hover_text("Move the apps back and restore the default").clicked()
+                {
+                    self.undo_send_all();
+                }
+            });
+        }
+        if let Some(route) = &self.route_move {
+            let done = route.done.load(Ordering::Acquire);
+            if done >= route.total {
+                let failure = route.failure.lock().unwrap_or_else(|e| e.into_inner()).take();
+                self.route_move = None;
+                if let Some(e) = failure {
+                    self.report::<()>(Err(e));
+                }
+            } else {
+                ui.add(
+                    egui::ProgressBar::new(done as f32 / route.total as f32)
+                        .text(format!("Moving streams… {}/{}", done, route.total)),
+                );
+                ui.ctx().request_repaint_after(Duration::from_millis(50));
+            }
+        }
+        ui.separator();
+    }
+
+    /// "🔋 70%" after a wireless device's name, when its battery is known.
+    fn battery_label(&self, ui: &mut egui::Ui, sink_name: &str) {
+        if let Some(percent) = self.batteries.for_sink(sink_name) {
+            ui.weak(format!("🔋 {:.0}%", percent)).on_hover_text("Battery level reported by UPower");
+        }
+    }
+
+    fn recording_section(&mut self, ui: &mut egui::Ui) {
+        if self.recordings.is_empty() {
+            return;
+        }
+
+        ui.label("⏺ Recording:");
+        let mut indices: Vec<u32> = self.recordings.keys().copied().collect();
+        indices.sort();
+        for index in indices {
+            let name = pactl::resolve_display_name(&self.recordings[&index], &self.config.label_properties).to_string();
+            let mut vol = self.per_recording_volumes.get(&index).copied();
+            let (focused, scroll) = self.focus_state(Focus::Recording(index));
+            let changed = section(ui, focused, scroll, |ui| {
+                ui.label(format!("{} (index: {})", name, index));
+                match &mut vol {
+                    Some(vol) => ui.add(egui::Slider::new(vol, 0.0..=100.0).text("%")).changed(),
+                    None => {
+                        ui.label("No volume data.");
+                        false
+                    }
+                }
+            });
+            if changed && let Some(vol) = vol {
+                self.per_recording_volumes.insert(index, vol);
+                self.send_volume(VolumeTarget::Recording(index), vol);
+            }
+        }
+        ui.separator();
+    }
+
+    /// Event sounds get one remembered volume instead of a slider each: they
+    /// only exist for the length of a beep.
+    fn system_sounds_section(&mut self, ui: &mut egui::Ui) {
+        let mut fixed = self.config.system_sounds_volume.is_some();
+        let mut vol = self.config.system_sounds_volume.unwrap_or(30.0);
+        let changed = ui
+            .horizontal(|ui| {
+                let toggled = ui
+                    .checkbox(&mut fixed, "🔔 System sounds at")
+                    .on_hover_text("Beeps and notification sounds (event streams) get this volume whenever they play")
+                    .changed();
+                let moved = ui.add_enabled(fixed, egui::Slider::new(&mut vol, 0.0..=100.0).text("%")).changed();
+                if !self.system_sounds.is_empty() {
+                    ui.weak(format!("{} playing", self.system_sounds.len()));
+                }
+                toggled || moved
+            })
+            .inner;
+        if changed {
+            self.config.system_sounds_volume = fixed.then_some(vol);
+            self.config.save_later();
+            if fixed {
+                let indices: Vec<u32> = self.system_sounds.keys().copied().collect();
+                for index in indices {
+                    self.send_volume(VolumeTarget::App(index), vol);
+                }
+            }
+        }
+        ui.separator();
+    }
+
+    /// Read-only: commands for other servers would need `--server` and
+    /// usually aren't permitted anyway.
+    fn other_sessions_section(&mut self, ui: &mut egui::Ui) {
+        if !self.config.show_other_sessions {
+            return;
+        }
+        ui.label("👥 Other sessions (read-only):");
+        if self.other_sessions.is_empty() {
+            ui.weak("No other sound servers found.");
+        }
+        for (server, streams) in &self.other_sessions {
+            ui.group(|ui| {
+                ui.monospace(server.display().to_string());
+                match streams {
+                    Err(e) => {
+                        ui.weak(format!("Not accessible: {}", e));
+                    }
+                    Ok(streams) if streams.is_empty() => {
+                        ui.weak("No streams.");
+                    }
+                    Ok(streams) => {
+                        let mut indices: Vec<&u32> = streams.keys().collect();
+                        indices.sort();
+                        for index in indices {
+                            let props = &streams[index];
+                            let name = pactl::resolve_display_name(props, &self.config.label_properties);
+                            let volume = props
+                                .get("Volume")
+                                .and_then(|v| parse_volume_percent(v, self.config.channel_mode))
+                                .map_or_else(|| "?".to_string(), |v| format!("{:.0}%", v));
+                            let muted = if props.get("Mute").is_some_and(|m| m == "yes") { " 🔇" } else { "" };
+                            ui.label(format!("{} (index: {}): {}{}", name, index, volume, muted));
+                        }
+                    }
+                }
+            });
+        }
+        ui.separator();
+    }
+
+    /// Switch the app list between compact rows and full controls.
+    fn toggle_compact_apps(&mut self) {
+        self.config.compact_apps = !self.config.compact_apps;
+        self.compact_expanded.clear();
+        self.config.save_later();
+    }
+
+    fn apps_section(&mut self, ui: &mut egui::Ui) {
+        // App Sliders
+        ui.horizontal(|ui| {
+            ui.label("🎶 Application Volumes:");
+            let (label, hint) = if self.config.compact_apps {
+                ("☰ Full", "Show every app's controls (F2)")
+            } else {
+                ("▤ Compact", "One read-only row per app, for monitoring (F2)")
+            };
+            if ui.small_button(label).on_hover_text(hint).clicked() {
+                self.toggle_compact_apps();
+            }
+        });
+        if !self.equalizer.available {
+            ui.weak("Equalizer: module-equalizer-sink not installed");
+        }
+        if !self.loaded {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.weak("Looking for applications…");
+            });
+            return;
+        }
+        if self.apps.is_empty() && self.lingering.is_empty() {
+            ui.vertical_centered(|ui| {
+                ui.add_space(12.0);
+                ui.label(egui::RichText::new("No applications are playing audio").heading());
+                ui.weak("Start playing something (a video, music, a call) and it shows up here with its own slider.");
+                ui.add_space(12.0);
+            });
+            return;
+        }
+        self.boost_panel(ui);
+        // Iterate in a stable, sorted order to avoid widgets jumping positions
+        let mut pids: Vec<u32> = self.apps.keys().chain(self.lingering.keys()).cloned().collect();
+        pids.sort();
+
+        let outputs = self.apps_by_output(&pids);
+        if !self.config.group_apps_by_output || outputs.len() < 2 {
+            for pid in pids {
+                self.app_entry(ui, pid);
+            }
+            return;
+        }
+        for (sink, pids) in outputs {
+            let label = match sink.and_then(|index| self.sinks.iter().find(|s| s.index == index)) {
+                Some(s) => self.config.sink_label(&s.name, &s.description).to_string(),
+                None => "Unknown output".to_string(),
+            };
+            egui::CollapsingHeader::new(format!("🔈 {} ({})", label, pids.len()))
+                .id_source(("output_group", sink))
+                .default_open(true)
+                .show(ui, |ui| {
+                    for pid in pids {
+                        self.app_entry(ui, pid);
+                    }
+                });
+        }
+    }
+
+    /// `pids` bucketed by the sink they play on, the default output first and
+    /// the rest by name.
+    fn apps_by_output(&self, pids: &[u32]) -> Vec<(Option<u32>, Vec<u32>)> {
+        let mut outputs: Vec<(Option<u32>, Vec<u32>)> = Vec::new();
+        for pid in pids {
+            let props = self.apps.get(pid).or_else(|| self.lingering.get(pid).map(|l| &l.props));
+            let sink = props.and_then(|props| self.stream_sink(props)).map(|s| s.index);
+            match outputs.iter_mut().find(|(s, _)| *s == sink) {
+                Some((_, pids)) => pids.push(*pid),
+                None => outputs.push((sink, vec![*pid])),
+            }
+        }
+        let sink = |index: Option<u32>| index.and_then(|index| self.sinks.iter().find(|s| s.index == index));
+        outputs.sort_by_key(|(index, _)| {
+            let sink = sink(*index);
+            let is_default = sink.is_some_and(|s| Some(&s.name) == self.default_sink.as_ref());
+            (!is_default, sink.is_none(), sink.map(|s| self.config.sink_label(&s.name, &s.description).to_lowercase()))
+        });
+        outputs
+    }
+
+    /// One app in the list: lingering, compact or with its full controls.
+    fn app_entry(&mut self, ui: &mut egui::Ui, pid: u32) {
+        if let Some(lingering) = self.lingering.get(&pid) {
+            lingering_group(ui, pid, lingering, &self.config.label_properties);
+            ui.separator();
+            return;
+        }
+        let (focused, scroll) = self.focus_state(Focus::App(pid));
+        if self.config.compact_apps && !self.compact_expanded.contains(&pid) {
+            section(ui, focused, scroll, |ui| self.compact_app_row(ui, pid));
+            return;
+        }
+        section(ui, focused, scroll, |ui| {
+            if self.config.compact_apps && ui.small_button("⏶ Back to the compact row").clicked() {
+                self.compact_expanded.remove(&pid);
+            }
+            self.app_group(ui, pid);
+        });
+        ui.separator();
+    }
+
+    /// An app in the compact view: name, volume, mute state and meter, with
+    /// nothing to drag. Clicking the name opens the full controls.
+    fn compact_app_row(&mut self, ui: &mut egui::Ui, pid: u32) {
+        let name = pactl::resolve_display_name(&self.apps[&pid], &self.config.label_properties);
+        ui.horizontal(|ui| {
+            let volume = self.per_app_volumes.get(&pid).map_or_else(|| "–".to_string(), |v| format!("{:.0}%", v));
+            ui.add_sized([40.0, 18.0], egui::Label::new(egui::RichText::new(volume).monospace()));
+            let muted = self.per_app_muted.get(&pid).copied().unwrap_or(false);
+            ui.label(if muted { "🔇" } else { "🔊" });
+            let peak = self.per_app_peak.get(&pid).copied().unwrap_or(0.0);
+            ui.add(egui::ProgressBar::new(peak).desired_width(80.0).desired_height(6.0));
+            if ui.selectable_label(false, name).on_hover_text("Show the controls").clicked() {
+                self.compact_expanded.insert(pid);
+            }
+        });
+    }
+
+    /// Groups of apps the user put together, each with one slider that sets
+    /// every member's streams. Members are remembered by app name.
+    fn custom_groups_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("🔗 Custom groups").show(ui, |ui| {
+            let mut running: Vec<String> =
+                self.apps.values().filter_map(|p| pactl::app_key(p)).map(str::to_string).collect();
+            running.sort();
+            running.dedup();
+
+            let mut changed = false;
+            let mut delete = None;
+            let names: Vec<String> = self.config.custom_groups.keys().cloned().collect();
+            for name in names {
+                let members = self.config.custom_groups[&name].clone();
+                let pids: Vec<u32> = self
+                    .apps
+                    .iter()
+                    .filter(|(_, props)| pactl::app_key(props).is_some_and(|key| members.iter().any(|m| m == key)))
+                    .map(|(pid, _)| *pid)
+                    .collect();
+                let volumes: Vec<f32> = pids.iter().filter_map(|pid| self.per_app_volumes.get(pid)).copied().collect();
+
+                egui::Frame::group(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.strong(&name);
+                        let id = egui::Id::new(("delete_group", &name));
+                        let timeout = self.config.confirm_timeout_secs;
+                        if confirm_button(ui, &mut self.confirming, timeout, id, |ui| {
+                            ui.small_button("🗑").on_hover_text("Delete this group")
+                        }) {
+                            delete = Some(name.clone());
+                        }
+                    });
+
+                    if volumes.is_empty() {
+                        ui.weak("None of its apps are playing.");
+                    } else {
+                        // The members' average; moving it sets them all to the same level.
+                        let mut vol = volumes.iter().sum::<f32>() / volumes.len() as f32;
+                        if ui.add(egui::Slider::new(&mut vol, 0.0..=100.0).text("%")).changed() {
+                            for pid in &pids {
+                                self.change_app_volume(*pid, vol);
+                            }
+                        }
+                    }
+
+                    let mut remove = None;
+                    ui.horizontal_wrapped(|ui| {
+                        for member in &members {
+                            let label = if running.contains(member) {
+                                egui::RichText::new(member)
+                            } else {
+                                egui::RichText::new(member).weak()
+                            };
+                            ui.label(label);
+                            if ui.small_button("✖").on_hover_text("Remove from the group").clicked() {
+                                remove = Some(member.clone());
+                            }
+                        }
+                    });
+
+                    let mut add = None;
+                    egui::ComboBox::from_id_source(("custom_group_add", &name))
+                        .selected_text("Add app…")
+                        .show_ui(ui, |ui| {
+                            for app in running.iter().filter(|app| !members.contains(app)) {
+                                if ui.selectable_label(false, app).clicked() {
+                                    add = Some(app.clone());
+                                }
+                            }
+                        });
+
+                    if let Some(group) = self.config.custom_groups.get_mut(&name) {
+                        if let Some(member) = remove {
+                            group.retain(|m| *m != member);
+                            changed = true;
+                        }
+                        if let Some(app) = add {
+                            group.push(app);
+                            changed = true;
+                        }
+                    }
+                });
+            }
+            if let Some(name) = delete {
+                self.config.custom_groups.remove(&name);
+                changed = true;
+            }
+
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.group_name_edit).hint_text("Group name, e.g. Voice chat"));
+                let name = self.group_name_edit.trim().to_string();
+                let valid = !name.is_empty() && !self.config.custom_groups.contains_key(&name);
+                if ui.add_enabled(valid, egui::Button::new("Create")).clicked() {
+                    self.config.custom_groups.insert(name, Vec::new());
+                    self.group_name_edit.clear();
+                    changed = true;
+                }
+            });
+
+            if changed {
+                self.config.save_later();
+            }
+        });
+    }
+
+    fn app_group(&mut self, ui: &mut egui::Ui, pid: u32) {
+        let props = self.apps[&pid].clone();
+        let primary = pactl::resolve_display_name(&props, &self.config.label_properties);
+        let app_key = pactl::app_key(&props).map(str::to_string);
+
+        let stream_spec = props.get("Sample Specification").map(String::as_str);
+        let sink_spec = self.stream_sink(&props).map(|s| s.sample_spec.as_str());
+        let resampling = stream_spec.zip(sink_spec).and_then(|(from, to)| pactl::resampling_note(from, to));
+        let mut format = String::new();
+        if let Some(spec) = stream_spec {
+            format.push_str(&format!("Stream format: {}", spec));
+        }
+        let channel_map = props.get("Channel Map");
+        if let Some(map) = channel_map {
+            format.push_str(&format!("\nChannels: {}", map.replace(',', ", ")));
+        }
+        if let Some(spec) = sink_spec.filter(|s| !s.is_empty()) {
+            format.push_str(&format!("\nOutput format: {}", spec));
+        }
+        if let Some(note) = &resampling {
+            format.push_str(&format!("\nResampling {}", note));
+        }
+        let color = app_key.as_deref().and_then(|app| self.config.app_color(app));
+        let mut color_change = None;
+        let selected = self.selected_apps.contains(&pid);
+        let mut toggle_selected = false;
+        let names: Vec<&str> = ["application.process.binary", "application.name"]
+            .iter()
+            .filter_map(|key| props.get(*key).map(String::as_str))
+            .collect();
+        let player = self.players.find(self.sink_input_pid(pid), &names);
+        let paused = props.get("Corked").is_some_and(|c| c == "yes");
+        let mut toggle_playback = false;
+        let header = |ui: &mut egui::Ui| {
+            let text = format!("{}{} (pid: {})", if selected { "☑ " } else { "" }, primary, pid);
+            let mut label = ui.add(egui::Label::new(text).sense(egui::Sense::click()));
+            toggle_selected = label.clicked() && ui.input(|i| i.modifiers.command);
+            if !format.is_empty() {
+                label = label.on_hover_text(format.trim_start());
+            }
+            if app_key.is_some() {
+                label.context_menu(|ui| {
+                    if let Some(tag) = color_tag_menu(ui, color) {
+                        color_change = Some(tag);
+                    }
+                });
+            }
+            if player.is_some() {
+                toggle_playback = ui
+                    .small_button(if paused { "▶" } else { "⏸" })
+                    .on_hover_text(if paused { "Resume playback" } else { "Pause playback" })
+                    .clicked();
+            }
+            if let Some(map) = channel_map
+                && let Some(layout) = pactl::channel_layout(map)
+            {
+                ui.weak(egui::RichText::new(layout).small()).on_hover_text(map.replace(',', ", "));
+            }
+            if let Some(note) = &resampling {
+                ui.weak("⟳").on_hover_text(format!("Resampling {}", note));
+            }
+            if let Some((label, reason)) = inactive_reason(&props, self.peaks.recent_peak(pid)) {
+                ui.weak(label).on_hover_text(reason);
+            }
+        };
+
+        if self.config.collapsible_app_groups {
+            egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), app_group_id(pid), true)
+                .show_header(ui, header)
+                .body(|ui| self.app_controls(ui, pid, &props, primary, &app_key));
+        } else {
+            ui.horizontal(header);
+            self.app_controls(ui, pid, &props, primary, &app_key);
+        }
+
+        if let Some([r, g, b]) = color {
+            // A stripe in the group frame's left margin.
+            let rect = ui.min_rect();
+            let stripe = egui::Rect::from_x_y_ranges(rect.left() - 5.0..=rect.left() - 2.0, rect.y_range());
+            ui.painter().rect_filled(stripe, 1.0, egui::Color32::from_rgb(r, g, b));
+        }
+        if let (Some(app), Some(tag)) = (&app_key, color_change) {
+            self.config.set_app_color(app, tag);
+            self.config.save_later();
+        }
+        if toggle_selected && !self.selected_apps.remove(&pid) {
+            self.selected_apps.insert(pid);
+        }
+        if let Some(player) = player.filter(|_| toggle_playback) {
+            mpris::play_pause(&player);
+        }
+    }
+
+    /// Background for an app's slider row: highlighted right after another
+    /// program changed its volume, fading out over `FLASH_SECS`.
+    fn flash_frame(&self, ui: &egui::Ui, pid: u32) -> egui::Frame {
+        let frame = egui::Frame::none().rounding(ui.visuals().widgets.noninteractive.rounding);
+        let Some(since) = self.app_flashes.get(&pid) else {
+            return frame;
+        };
+        let left = 1.0 - since.elapsed().as_secs_f32() / FLASH_SECS;
+        if left <= 0.0 {
+            return frame;
+        }
+        ui.ctx().request_repaint();
+        frame
+            .fill(ui.visuals().selection.bg_fill.gamma_multiply(left))
+    }
+
+    /// Everything in an app's group below its name.
+    fn app_controls(&mut self, ui: &mut egui::Ui, pid: u32, props: &Props, primary: &str, app_key: &Option<String>) {
+        // Show secondary info when available and different from primary
+        if let Some(app_name) = props.get("application.name")
+            && app_name != primary
+        {
+            ui.label(format!("App: {}", app_name));
+        }
+        if let Some(media_title) = props.get("media.name")
+            && media_title != primary
+        {
+            ui.label(format!("Title: {}", media_title));
+        }
+        if self.config.show_window_titles
+            && let Some(window_title) = self.sink_input_pid(pid).and_then(|id| self.window_titles.for_process(id))
+            && window_title != primary
+            && props.get("media.name") != Some(&window_title)
+        {
+            ui.label(format!("Window: {}", window_title));
+        }
+
+        if let Some(mut vol) = self.per_app_volumes.get(&pid).copied() {
+            // Glide to volumes set elsewhere; follow the user's own drags exactly.
+            let glide = match self.app_sources.get(&pid) {
+                Some(ChangeSource::External) => APP_ANIMATION_SECS,
+                _ => 0.0,
+            };
+            vol = ui.ctx().animate_value_with_time(egui::Id::new(("app_volume", pid)), vol, glide);
+            let (slider, mute) = self
+                .flash_frame(ui, pid)
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        let slider = ui.add(egui::Slider::new(&mut vol, 0.0..=100.0).text("%"));
+                        let muted = self.per_app_muted.get(&pid).copied().unwrap_or(false);
+                        let mute = ui
+                            .selectable_label(muted, if muted { "🔇" } else { "🔊" })
+                            .on_hover_text(if muted { "Unmute" } else { "Mute" });
+                        (slider, mute.clicked().then_some(!muted))
+                    })
+                    .inner
+                })
+                .inner;
+            let slider = match props.get("Volume").filter(|_| self.config.show_raw_volumes) {
+                Some(raw) => slider.on_hover_text(raw_volume_text(
+                    &format!("{:.0}% ({})", vol, self.config.channel_mode.label().to_lowercase()),
+                    raw,
+                )),
+                None => slider,
+            };
+            if slider.changed() {
+                self.change_app_volume(pid, vol);
+            }
+            if slider.hovered() && !self.locked {
+                let scroll = ui.input(|i| i.raw_scroll_delta.y);
+                if scroll != 0.0 {
+                    // The wheel is ours here, not the page's.
+                    ui.input_mut(|i| i.smooth_scroll_delta = egui::Vec2::ZERO);
+                    self.scroll_apps(pid, VOLUME_STEP * scroll.signum());
+                }
+            }
+            if self.config.show_effective_volume
+                && let Some(sink_vol) = self.sink_volume(props)
+            {
+                ui.weak(format!("≈ {:.0}% effective", vol * sink_vol / 100.0))
+                    .on_hover_text(format!(
+                        "App {:.0}% × output {:.0}%. The slider still sets the app's own volume.",
+                        vol, sink_vol
+                    ));
+            }
+            if let Some(muted) = mute {
+                if muted && self.config.confirm_mute_playing && self.plays_media(pid, props) {
+                    self.mute_confirm = Some(pid);
+                } else {
+                    self.change_app_mute(pid, muted);
+                }
+            }
+            if self.mute_confirm == Some(pid) {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label(format!("{} is playing. Mute it anyway?", primary));
+                    if ui.small_button("Mute").clicked() {
+                        self.change_app_mute(pid, true);
+                        self.mute_confirm = None;
+                    }
+                    if ui.small_button("Cancel").clicked() {
+                        self.mute_confirm = None;
+                    }
+                });
+            }
+            if slider.drag_stopped()
+                && let Some(app) = app_key
+            {
+                self.offer_remember(app, vol);
+            }
+
+            if let Some(peak) = self.per_app_peak.get(&pid) {
+                ui.add(egui::ProgressBar::new(*peak).desired_height(6.0));
+                if self.config.show_loudness_bars {
+                    let muted = self.per_app_muted.get(&pid).copied().unwrap_or(false);
+                    let loudness = if muted { 0.0 } else { (peak * vol / 100.0).min(1.0) };
+                    ui.add(
+                        egui::ProgressBar::new(loudness)
+                            .desired_height(4.0)
+                            .fill(ui.visuals().warn_fg_color),
+                    )
+                    .on_hover_text(format!(
+                        "Loudness: level × volume = {:.0}% × {:.0}% = {:.0}%",
+                        peak * 100.0,
+                        vol,
+                        loudness * 100.0
+                    ));
+                }
+            }
+
+            let normalized = self
+                .peaks
+                .recent_peak(pid)
+                .and_then(|p| peak::normalized_volume(vol, p, NORMALIZE_TARGET_PEAK, 100.0));
+            let button = ui
+                .add_enabled(normalized.is_some(), egui::Button::new("Normalize peak"))
+                .on_hover_text("Set the volume so this app's loudest moment in the last 10 s reaches -6 dBFS")
+                .on_disabled_hover_text("No sound measured recently");
+            if button.clicked()
+                && let Some(target) = normalized
+            {
+                self.change_app_volume(pid, target);
+                self.peaks.clear_history(pid);
+            }
+        } else {
+            ui.label("No volume data.");
+        }
+
+        if let Some(app) = app_key {
+            let sink = self.stream_sink(props).map(|s| s.name.clone());
+            self.remember_controls(ui, app, sink.as_deref());
+
+            let mut follow = self.config.follows_system_mute(app);
+            if ui
+                .checkbox(&mut follow, "Mute with the system")
+                .on_hover_text(format!("Mute {} too while the system is muted, and unmute it after", app))
+                .changed()
+            {
+                self.config.set_follow_system_mute(app, follow);
+                self.config.save_later();
+                self.sync_mute_followers();
+            }
+        }
+
+        self.latency_controls(ui, pid, props);
+        self.role_controls(ui, pid, props);
+        self.rate_match_controls(ui, pid, props);
+
+        let sink = props.get("Sink").cloned().unwrap_or_default();
+        if self.sinks.len() > 1 {
+            let mut everywhere = self.broadcast.is_active(pid);
+            let toggle = ui
+                .checkbox(&mut everywhere, "📢 Play on all outputs")
+                .on_hover_text("Duplicate this app to every output device; untick to go back");
+            if toggle.changed() && everywhere {
+                self.post(None, self.broadcast.start(pid, &sink, &self.sinks));
+            } else if toggle.changed() {
+                self.post(None, self.broadcast.stop(pid));
+            }
+        }
+
+        if self.equalizer.available {
+            egui::CollapsingHeader::new("Equalizer (advanced)")
+                .id_source(("eq", pid))
+                .show(ui, |ui| {
+                    let current = self.equalizer.preset_for(pid);
+                    let mut selected = current;
+                    egui::ComboBox::from_id_source(("eq_preset", pid))
+                        .selected_text(current.unwrap_or("Off"))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut selected, None, "Off");
+                            for preset in equalizer::PRESETS {
+                                ui.selectable_value(&mut selected, Some(preset), preset);
+                            }
+                        });
+                    if selected != current {
+                        self.post(None, self.equalizer.set_preset(pid, selected, &sink));
+                    }
+                });
+        }
+    }
+
+    /// Experimental per-stream latency nudge for A/V sync, for streams that
+    /// expose their PipeWire node id and current `node.latency`.
+    fn latency_controls(&mut self, ui: &mut egui::Ui, pid: u32, props: &Props) {
+        let Some(node_id) = props.get("object.id").and_then(|id| id.parse::<u32>().ok()) else {
+            return;
+        };
+        let Some((frames, rate)) = props.get("node.latency").and_then(|l| pactl::parse_latency(l)) else {
+            return;
+        };
+
+        egui::CollapsingHeader::new("Latency (experimental)")
+            .id_source(("latency", pid))
+            .show(ui, |ui| {
+                ui.label(format!(
+                    "Current: {} frames @ {} Hz ({:.1} ms)",
+                    frames,
+                    rate,
+                    frames as f32 * 1000.0 / rate as f32
+                ));
+                ui.horizontal(|ui| {
+                    let edit = self.latency_edits.entry(pid).or_insert(frames);
+                    ui.add(egui::DragValue::new(edit).clamp_range(32..=8192).speed(16).suffix(" frames"));
+                    ui.label(format!("{:.1} ms", *edit as f32 * 1000.0 / rate as f32));
+                    if ui.button("Apply").clicked() {
+                        let frames = *edit;
+                        self.post(None, move || pactl::set_node_latency(node_id, frames, rate));
+                    }
+                });
+                ui.weak("Changes the node's requested latency; not every app or driver follows it.");
+            });
+    }
+
+    /// Offer to move a resampled stream to an output already running at its
+    /// sample rate, so it plays bit-exact. Only shown when one exists.
+    fn rate_match_controls(&mut self, ui: &mut egui::Ui, pid: u32, props: &Props) {
+        let Some(stream_spec) = props.get("Sample Specification") else {
+            return;
+        };
+        let current = self.stream_sink(props);
+        if current.is_none_or(|s| pactl::resampling_note(stream_spec, &s.sample_spec).is_none()) {
+            return;
+        }
+        let current = current.map(|s| s.index);
+        let candidates: Vec<(String, String, String)> = pactl::same_rate_sinks(stream_spec, &self.sinks)
+            .into_iter()
+            .filter(|s| Some(s.index) != current)
+            .map(|s| {
+                let label = self.config.sink_label(&s.name, &s.description).to_string();
+                (s.name.clone(), label, s.sample_spec.clone())
+            })
+            .collect();
+        if candidates.is_empty() {
+            return;
+        }
+
+        egui::CollapsingHeader::new("Avoid resampling (advanced)")
+            .id_source(("rate_match", pid))
+            .show(ui, |ui| {
+                ui.weak(format!("This stream is {}; these outputs run at its rate:", stream_spec));
+                for (name, label, spec) in candidates {
+                    ui.horizontal(|ui| {
+                        ui.label(label).on_hover_text(spec);
+                        if ui.button("Move here").clicked() {
+                            self.post(None, move || pactl::move_sink_input(pid, &name));
+                        }
+                    });
+                }
+            });
+    }
+
+    /// Whether `pid` is playing music or video right now: not paused, with a
+    /// media role (possibly set here) of music, movie or video.
+    fn plays_media(&self, pid: u32, props: &Props) -> bool {
+        if props.get("Corked").is_some_and(|c| c == "yes") {
+            return false;
+        }
+        let role = match self.role_overrides.get(&pid) {
+            Some(role) => role.as_deref(),
+            None => props.get("media.role").map(String::as_str),
+        };
+        role.is_some_and(|role| ["music", "movie", "video"].iter().any(|r| role.eq_ignore_ascii_case(r)))
+    }
+
+    /// Media role and routing flags. Advanced: the role decides where the
+    /// session manager routes (and ducks) the stream.
+    fn role_controls(&mut self, ui: &mut egui::Ui, pid: u32, props: &Props) {
+        let Some(node_id) = props.get("object.id").and_then(|id| id.parse::<u32>().ok()) else {
+            return;
+        };
+        // The metadata override doesn't show up in the stream's properties.
+        let role = match self.role_overrides.get(&pid) {
+            Some(role) => role.clone(),
+            None => props.get("media.role").cloned(),
+        };
+        let role = role.as_deref();
+
+        egui::CollapsingHeader::new("Role and routing flags (advanced)")
+            .id_source(("role", pid))
+            .show(ui, |ui| {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    "⚠ The role decides automatic routing, ducking and corking; a wrong one can send this app \
+                     to another device or silence it.",
+                );
+                ui.horizontal(|ui| {
+                    ui.label("Role:");
+                    let mut selected = role;
+                    egui::ComboBox::from_id_source(("role_select", pid))
+                        .selected_text(role.unwrap_or("(none)"))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut selected, None, "(none)");
+                            for candidate in pactl::MEDIA_ROLES {
+                                ui.selectable_value(&mut selected, Some(candidate), candidate);
+                            }
+                        });
+                    if selected != role {
+                        let selected = selected.map(str::to_string);
+                        self.role_overrides.insert(pid, selected.clone());
+                        self.post(None, move || pactl::set_media_role(node_id, selected.as_deref()));
+                    }
+                });
+                for flag in pactl::ROUTING_FLAGS {
+                    if let Some(value) = props.get(flag) {
+                        ui.monospace(format!("{} = {}", flag, value));
+                    }
+                }
+                if let Some(corked) = props.get("Corked") {
+                    ui.monospace(format!("corked = {}", corked));
+                }
+                ui.weak("Set through PipeWire metadata; the policy may only apply it when the stream reconnects.");
+            });
+    }
+
+    /// After the user settles on a volume for `app`, ask whether new streams of
+    /// it should start there, unless that's already saved or prompts are off.
+    fn offer_remember(&mut self, app: &str, vol: f32) {
+        if self.config.suppress_remember_prompt || self.config.app_volume(app) == Some(vol) {
+            return;
+        }
+        self.remember_prompt = Some((app.to_string(), vol));
+    }
+
+    /// The remember prompt when it's for `app`, otherwise the saved volume (if
+    /// any) with a way to forget it.
+    fn remember_controls(&mut self, ui: &mut egui::Ui, app: &str, sink: Option<&str>) {
+        let device_label = |this: &Self, device: &str| {
+            let description = this.sinks.iter().find(|s| s.name == device).map_or(device, |s| s.description.as_str());
+            this.config.sink_label(device, description).to_string()
+        };
+
+        if let Some((prompt_app, vol)) = self.remember_prompt.clone()
+            && prompt_app == app
+        {
+            ui.horizontal_wrapped(|ui| {
+                ui.label(format!("Start new {} streams at {:.0}%?", app, vol));
+                if ui.small_button("Remember").clicked() {
+                    self.config.set_app_volume(app, Some(vol));
+                    self.config.save_later();
+                    self.remember_prompt = None;
+                }
+                if let Some(sink) = sink
+                    && ui
+                        .small_button("Only on this output")
+                        .on_hover_text(format!("Use {:.0}% while {} plays on {}", vol, app, device_label(self, sink)))
+                        .clicked()
+                {
+                    self.config.set_device_volume(app, sink, Some(vol));
+                    self.config.save_later();
+                    self.remember_prompt = None;
+                }
+                if ui.small_button("Not now").clicked() {
+                    self.remember_prompt = None;
+                }
+                if ui.small_button("Don't ask again").clicked() {
+                    self.config.suppress_remember_prompt = true;
+                    self.config.save_later();
+                    self.remember_prompt = None;
+                }
+            });
+            return;
+        }
+
+        if let Some(saved) = self.config.app_volume(app) {
+            ui.horizontal(|ui| {
+                ui.weak(format!("⭐ New streams start at {:.0}%", saved));
+                if ui.small_button("Forget").clicked() {
+                    self.config.set_app_volume(app, None);
+                    self.config.save_later();
+                }
+            });
+        }
+        let devices: Vec<(String, f32)> = self
+            .config
+            .apps
+            .get(app)
+            .map(|a| a.devices.iter().map(|(d, v)| (d.clone(), *v)).collect())
+            .unwrap_or_default();
+        for (device, saved) in devices {
+            ui.horizontal(|ui| {
+                ui.weak(format!("⭐ On {}: {:.0}%", device_label(self, &device), saved));
+                if ui.small_button("Forget").clicked() {
+                    self.config.set_device_volume(app, &device, None);
+                    self.config.save_later();
+                }
+            });
+        }
+    }
+
+    /// Set the saved volumes due on this snapshot's streams (see
+    /// `saved_volume_changes`) and return them, as set.
+    fn apply_saved_volumes(&mut self) -> HashMap<u32, f32> {
+        let first = !self.loaded;
+        let changes = saved_volume_changes(&self.apps, &self.sinks, &mut self.seen_streams, &self.config, first);
+        let mut applied = HashMap::new();
+        for (pid, saved) in changes {
+            let saved = self.hold_boost(pid, saved);
+            self.send_volume(VolumeTarget::App(pid), saved);
+            applied.insert(pid, saved);
+        }
+        applied
+    }
+
+    /// Record a system volume reported by `wpctl`. The slider eases towards it
+    /// instead of jumping, unless it matches what is already shown.
+    fn set_external_volume(&mut self, sys_v: f32) {
+        if (sys_v - self.vol_target).abs() < f32::EPSILON {
+            return;
+        }
+        self.vol_target = sys_v;
+        self.vol_source = ChangeSource::External;
+    }
+
+    /// Move the displayed system volume towards `vol_target`. User-driven
+    /// changes are already in place; external ones are eased exponentially.
+    fn smooth_volume(&mut self, ctx: &egui::Context) {
+        if self.vol_source == ChangeSource::User || self.vol == self.vol_target {
+            return;
+        }
+
+        let dt = ctx.input(|i| i.stable_dt).min(0.1);
+        let alpha = 1.0 - (-dt / VOL_SMOOTHING_SECS).exp();
+        self.vol += (self.vol_target - self.vol) * alpha;
+
+        if (self.vol_target - self.vol).abs() < 0.05 {
+            self.vol = self.vol_target;
+        } else {
+            ctx.request_repaint();
+        }
+    }
+}
+
+/// A group frame, outlined with the selection colour when it has keyboard
+/// focus and scrolled into view when `scroll` is set.
+fn section<R>(ui: &mut egui::Ui, focused: bool, scroll: bool, add_contents: impl FnOnce(&mut egui::Ui) -> R) -> R {
+    let mut frame = egui::Frame::group(ui.style());
+    if focused {
+        frame.stroke = ui.visuals().selection.stroke;
+    }
+    let response = frame.show(ui, add_contents);
+    if scroll {
+        response.response.scroll_to_me(None);
+    }
+    response.inner
+}
+
+/// `current` moved by `delta` percent, clamped only at the bound it moves
+/// towards (100 going up, 0 going down), or `None` when it is already at or
+/// past that bound. A volume boosted above 100% steps down from where it is.
+fn stepped_volume(current: f32, delta: f32) -> Option<f32> {
+    if (delta > 0.0 && current >= 100.0) || (delta < 0.0 && current <= 0.0) {
+        return None;
+    }
+    let next = if delta > 0.0 { (current + delta).min(100.0) } else { (current + delta).max(0.0) };
+    (next != current).then_some(next)
+}
+
+/// The volume commands stepping `targets` by `delta` sends: one for each
+/// target with a volume (`current`) that isn't yet at its bound.
+fn volume_steps(
+    targets: &[VolumeTarget],
+    delta: f32,
+    current: impl Fn(VolumeTarget) -> Option<f32>,
+) -> Vec<(VolumeTarget, f32)> {
+    targets
+        .iter()
+        .filter_map(|&target| Some((target, stepped_volume(current(target)?, delta)?)))
+        .collect()
+}
+
+fn show_main_window(ctx: &egui::Context) {
+    ctx.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+    ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+    ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+}
+
+/// A button for something destructive. The first click turns it into
+/// "Sure? Yes / No", which cancels itself after `timeout_secs` (0: never) so
+/// an unattended window isn't left one click from it. True on "Yes".
+fn confirm_button(
+    ui: &mut egui::Ui,
+    pending: &mut Option<Confirmation>,
+    timeout_secs: f32,
+    id: egui::Id,
+    button: impl FnOnce(&mut egui::Ui) -> egui::Response,
+) -> bool {
+    let asking = pending.as_ref().filter(|c| c.id == id).map(|c| c.since.elapsed().as_secs_f32());
+    match asking {
+        Some(elapsed) if timeout_secs <= 0.0 || elapsed < timeout_secs => {
+            ui.label("Sure?");
+            let yes = ui.small_button("Yes").clicked();
+            if yes || ui.small_button("No").clicked() {
+                *pending = None;
+            }
+            if timeout_secs > 0.0 {
+                ui.weak(format!("{:.0} s", (timeout_secs - elapsed).ceil()));
+                ui.ctx().request_repaint_after(Duration::from_millis(250));
+            }
+            yes
+        }
+        asking => {
+            if asking.is_some() {
+                *pending = None; // timed out
+            }
+            if button(ui).clicked() {
+                *pending = Some(Confirmation { id, since: std::time::Instant::now() });
+            }
+            false
+        }
+    }
+}
+
+/// Whether the sound server restarted (or went away) between two polls: a
+/// sink that is still there under the same name got a new index, or every
+/// sink disappeared at once.
+fn server_restarted(prev: &[Sink], next: &[Sink]) -> bool {
+    if prev.is_empty() {
+        return false;
+    }
+    next.is_empty() || prev.iter().any(|p| next.iter().any(|n| n.name == p.name && n.index != p.index))
+}
+
+/// The sink a sink input plays on.
+fn sink_of<'a>(props: &Props, sinks: &'a [Sink]) -> Option<&'a Sink> {
+    let index = props.get("Sink")?.parse::<u32>().ok()?;
+    sinks.iter().find(|s| s.index == index)
+}
+
+/// Saved volumes due on a snapshot's streams: streams that appeared since the
+/// last one get their app's saved volume for the output they play on, and
+/// streams that moved to another output (e.g. after the default changed) the
+/// volume saved for that output, if any. Streams already playing when the
+/// mixer started (`first`) are left alone. `seen` is where each stream played
+/// at the last call; it is updated, and streams that went away are dropped.
+fn saved_volume_changes(
+    apps: &HashMap<u32, Props>,
+    sinks: &[Sink],
+    seen: &mut HashMap<u32, Option<String>>,
+    config: &Config,
+    first: bool,
+) -> Vec<(u32, f32)> {
+    let mut changes = Vec::new();
+    for (pid, props) in apps {
+        let sink = sink_of(props, sinks).map(|s| s.name.clone());
+        let previous = seen.insert(*pid, sink.clone());
+        let Some(app) = pactl::app_key(props).filter(|_| !first) else {
+            continue;
+        };
+        let saved = match previous {
+            None => config.app_volume_on(app, sink.as_deref()),
+            Some(previous) if previous != sink => sink.and_then(|s| config.device_volume(app, &s)),
+            Some(_) => None,
+        };
+        if let Some(saved) = saved {
+            changes.push((*pid, saved));
+        }
+    }
+    changes.sort_by_key(|(pid, _)| *pid);
+    seen.retain(|pid, _| apps.contains_key(pid));
+    changes
+}
+
+/// What each stream shows, by precedence: a session override, then a saved
+/// volume just applied, then the live value the server reported. Only
+/// streams the server still reports are kept.
+fn merge_layers(
+    live_volumes: &HashMap<u32, f32>,
+    live_muted: &HashMap<u32, bool>,
+    saved: &HashMap<u32, f32>,
+    overrides: &HashMap<u32, SessionOverride>,
+) -> (HashMap<u32, f32>, HashMap<u32, bool>) {
+    let volumes = live_volumes
+        .iter()
+        .map(|(pid, live)| {
+            let session = overrides.get(pid).and_then(|o| o.volume);
+            (*pid, session.or_else(|| saved.get(pid).copied()).unwrap_or(*live))
+        })
+        .collect();
+    let muted = live_muted
+        .iter()
+        .map(|(pid, live)| (*pid, overrides.get(pid).and_then(|o| o.muted).unwrap_or(*live)))
+        .collect();
+    (volumes, muted)
+}
+
+/// The value of a read for a snapshot, or an empty one when it failed. The
+/// first failure is kept in `error`.
+fn noted<T: Default>(result: Result<T, VolumeError>, error: &mut Option<VolumeError>) -> T {
+    result.unwrap_or_else(|e| {
+        error.get_or_insert(e);
+        T::default()
+    })
+}
+
+/// Pair pending restores with new streams of the same app, one stream each,
+/// in index order. Matched restores are taken out of `pending`.
+fn match_restores(
+    pending: &mut Vec<PendingRestore>,
+    apps: &HashMap<u32, Props>,
+    new_pids: &[u32],
+) -> Vec<(u32, PendingRestore)> {
+    let mut matched = Vec::new();
+    for pid in new_pids {
+        let Some(app) = apps.get(pid).and_then(pactl::app_key) else {
+            continue;
+        };
+        if let Some(i) = pending.iter().position(|r| r.app == app) {
+            matched.push((*pid, pending.remove(i)));
+        }
+    }
+    matched
+}
+
+/// Whether a stream belongs to `app`, by application name or binary,
+/// ignoring case.
+fn stream_is_app(props: &Props, app: &str) -> bool {
+    [pactl::app_key(props), props.get("application.process.binary").map(String::as_str)]
+        .into_iter()
+        .flatten()
+        .any(|name| name.eq_ignore_ascii_case(app))
+}
+
+/// Streams that auto-mute rules say should be muted right now: those of each
+/// rule's `mute` app while a stream of its `when` app is `playing`.
+fn auto_mute_targets(
+    rules: &[AutoMuteRule],
+    apps: &HashMap<u32, Props>,
+    playing: impl Fn(u32) -> bool,
+) -> HashSet<u32> {
+    let mut targets = HashSet::new();
+    for rule in rules {
+        let triggered = apps.iter().any(|(pid, props)| stream_is_app(props, &rule.when) && playing(*pid));
+        if triggered {
+            targets.extend(apps.iter().filter(|(_, props)| stream_is_app(props, &rule.mute)).map(|(pid, _)| *pid));
+        }
+    }
+    targets
+}
+
+/// One line for the tray tooltip, e.g. "40% · 2 apps playing". `volume` is
+/// None without an output device.
+fn tray_status(volume: Option<f32>, muted: bool, playing: usize) -> String {
+    let system = match volume {
+        None => "No output device".to_string(),
+        Some(vol) if muted => format!("Muted ({:.0}%)", vol),
+        Some(vol) => format!("{:.0}%", vol),
+    };
+    let apps = match playing {
+        0 => "nothing playing".to_string(),
+        1 => "1 app playing".to_string(),
+        n => format!("{} apps playing", n),
+    };
+    format!("{} · {}", system, apps)
+}
+
+/// A stream that just went away: its name and last volume, greyed out.
+fn lingering_group(ui: &mut egui::Ui, pid: u32, lingering: &Lingering, label_properties: &[String]) {
+    let name = pactl::resolve_display_name(&lingering.props, label_properties);
+    ui.add_enabled_ui(false, |ui| {
+        ui.horizontal(|ui| {
+            ui.label(format!("{} (pid: {})", name, pid));
+            ui.weak("closed");
+        });
+        let mut volume = lingering.volume.unwrap_or(0.0);
+        ui.add(egui::Slider::new(&mut volume, 0.0..=100.0).text("%"));
+    });
+}
+
+/// A slider tooltip: the value `shown` and the raw `Volume:` value behind it,
+/// one channel per line.
+fn raw_volume_text(shown: &str, raw: &str) -> String {
+    let mut text = format!("Shown: {}", shown);
+    for channel in pactl::parse_channel_volumes(raw) {
+        text.push_str(&format!(
+            "\n{}: {} / {:.0}% / {:.2} dB",
+            channel.channel, channel.raw, channel.percent, channel.db
+        ));
+    }
+    text.push_str("\n(65536 = 100%; percentages follow a cubic curve, not dB)");
+    text
+}
+
+/// Id of an app's collapsible group, shared by the UI and the auto-expand.
+fn app_group_id(pid: u32) -> egui::Id {
+    egui::Id::new(("app_group", pid))
+}
+
+fn set_app_group_open(ctx: &egui::Context, pid: u32, open: bool) {
+    let mut state = egui::collapsing_header::CollapsingState::load_with_default_open(ctx, app_group_id(pid), true);
+    state.set_open(open);
+    state.store(ctx);
+}
+
+/// Where to put a `size` popup for a tray click at `click`: centred on it
+/// horizontally, below it for a panel at the top of the screen and above it
+/// otherwise, kept on the monitor when its size is known.
+fn popup_position(click: egui::Pos2, size: egui::Vec2, monitor: Option<egui::Vec2>) -> egui::Pos2 {
+    const GAP: f32 = 8.0;
+    let mut pos = egui::pos2(click.x - size.x / 2.0, click.y + GAP);
+    if let Some(monitor) = monitor {
+        if click.y > monitor.y / 2.0 {
+            pos.y = click.y - size.y - GAP;
+        }
+        pos.x = pos.x.clamp(0.0, (monitor.x - size.x).max(0.0));
+        pos.y = pos.y.clamp(0.0, (monitor.y - size.y).max(0.0));
+    }
+    pos
+}
+
+/// Colors offered for app tags.
+const TAG_COLORS: [(&str, [u8; 3]); 8] = [
+    ("Red", [0xe0, 0x4f, 0x4f]),
+    ("Orange", [0xe8, 0x8f, 0x3c]),
+    ("Yellow", [0xe3, 0xc5, 0x3a]),
+    ("Green", [0x5a, 0xb5, 0x5e]),
+    ("Teal", [0x3a, 0xaf, 0xa9]),
+    ("Blue", [0x4a, 0x86, 0xe0]),
+    ("Purple", [0x9b, 0x6a, 0xd8]),
+    ("Pink", [0xd8, 0x6a, 0xb0]),
+];
+
+/// The "Color tag" context menu of an app: a row of swatches plus a custom
+/// color and "None". Returns the new tag once one is picked; picking a
+/// swatch or "None" also closes the menu.
+fn color_tag_menu(ui: &mut egui::Ui, current: Option<[u8; 3]>) -> Option<Option<[u8; 3]>> {
+    let mut picked = None;
+    ui.label("Color tag");
+    ui.horizontal(|ui| {
+        for (name, [r, g, b]) in TAG_COLORS {
+            let swatch = egui::Button::new("")
+                .fill(egui::Color32::from_rgb(r, g, b))
+                .min_size(egui::vec2(18.0, 18.0))
+                .selected(current == Some([r, g, b]));
+            if ui.add(swatch).on_hover_text(name).clicked() {
+                picked = Some(Some([r, g, b]));
+                ui.close_menu();
+            }
+        }
+    });
+    ui.horizontal(|ui| {
+        let mut custom = current.unwrap_or([0x80, 0x80, 0x80]);
+        if ui.color_edit_button_srgb(&mut custom).changed() {
+            picked = Some(Some(custom));
+        }
+        ui.label("Custom");
+    });
+    if ui.add_enabled(current.is_some(), egui::Button::new("None")).clicked() {
+        picked = Some(None);
+        ui.close_menu();
+    }
+    picked
+}
+
+/// A short label and explanation when a stream looks inactive: paused by
+/// the app (corked), without a volume the server will report, or open but
+/// silent over the measured history (`recent_peak`, `None` if unmeasured).
+fn inactive_reason(props: &Props, recent_peak: Option<f32>) -> Option<(&'static str, &'static str)> {
+    if props.get("Corked").is_some_and(|c| c == "yes") {
+        return Some(("⏸ Paused", "The app paused (corked) this stream: it stays connected but sends no sound."));
+    }
+    if props.get("Volume").and_then(|v| parse_volume_percent(v, ChannelMode::default())).is_none() {
+        return Some((
+            "No volume",
+            "The server reports no volume for this stream, e.g. compressed audio passed through to a receiver. \
+             It can still be muted or moved.",
+        ));
+    }
+    if recent_peak.is_some_and(|p| p < ACTIVITY_OFF_PEAK) {
+        return Some(("💤 Idle", "Playing, but silent for the last 10 s: the app keeps the stream open without sound."));
+    }
+    None
+}
+
+/// Whether the server's default sink is a real, listed device. With every
+/// output unplugged PipeWire reports none (or a dummy `auto_null`) and
+/// `@DEFAULT_AUDIO_SINK@` commands silently do nothing.
+fn has_default_sink(default: Option<&str>, sinks: &[Sink]) -> bool {
+    default.is_some_and(|default| default != "auto_null" && sinks.iter().any(|s| s.name == default))
+}
+
+fn no_output_banner(ui: &mut egui::Ui) {
+    let color = ui.visuals().error_fg_color;
+    egui::Frame::none()
+        .fill(color.linear_multiply(0.15))
+        .rounding(4.0)
+        .inner_margin(8.0)
+        .show(ui, |ui| {
+            ui.label(egui::RichText::new("⚠ No output device").color(color).heading());
+            ui.label("Connect or enable a sound device; the controls come back once one appears.");
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stepping_stops_at_the_bounds() {
+        assert_eq!(stepped_volume(100.0, VOLUME_STEP), None);
+        assert_eq!(stepped_volume(0.0, -VOLUME_STEP), None);
+    }
+
+    #[test]
+    fn stepping_clamps_onto_the_bound_once() {
+        assert_eq!(stepped_volume(97.0, VOLUME_STEP), Some(100.0));
+        assert_eq!(stepped_volume(100.0, -VOLUME_STEP), Some(95.0));
+        assert_eq!(stepped_volume(3.0, -VOLUME_STEP), Some(0.0));
+    }
+
+    #[test]
+    fn stepping_up_never_lowers_an_overboosted_volume() {
+        assert_eq!(stepped_volume(120.0, VOLUME_STEP), None);
+        assert_eq!(stepped_volume(120.0, -VOLUME_STEP), Some(115.0));
+        assert_eq!(stepped_volume(102.0, -VOLUME_STEP), Some(97.0));
+    }
+
+    #[test]
+    fn no_command_is_sent_at_the_bound() {
+        let volumes = BTreeMap::from([
+            (VolumeTarget::System, 100.0),
+            (VolumeTarget::Mic, 0.0),
+            (VolumeTarget::App(1), 40.0),
+            (VolumeTarget::App(2), 120.0),
+        ]);
+        let current = |target| volumes.get(&target).copied();
+        let all = [VolumeTarget::System, VolumeTarget::Mic, VolumeTarget::App(1), VolumeTarget::App(2)];
+
+        let raised = volume_steps(&all, VOLUME_STEP, current);
+        assert_eq!(raised, vec![(VolumeTarget::Mic, 5.0), (VolumeTarget::App(1), 45.0)]);
+        assert_eq!(
+            volume_steps(&all, -VOLUME_STEP, current),
+            vec![(VolumeTarget::System, 95.0), (VolumeTarget::App(1), 35.0), (VolumeTarget::App(2), 115.0)]
+        );
+        // No volume (no output device, a stream without one): nothing to step.
+        assert!(volume_steps(&[VolumeTarget::Recording(7)], VOLUME_STEP, current).is_empty());
+        assert!(volume_steps(&[VolumeTarget::System], VOLUME_STEP, current).is_empty());
+    }
+
+    #[test]
+    fn auto_mute_follows_the_trigger() {
+        let app = |name: &str| -> Props { HashMap::from([("application.name".to_string(), name.to_string())]) };
+        let apps = HashMap::from([(1, app("Spotify")), (2, app("discord")), (3, app("Firefox"))]);
+        let rules = [AutoMuteRule {
+            mute: "spotify".to_string(),
+            when: "Discord".to_string(),
+        }];
+
+        assert_eq!(auto_mute_targets(&rules, &apps, |_| true), HashSet::from([1]));
+        assert!(auto_mute_targets(&rules, &apps, |pid| pid != 2).is_empty());
+    }
+
+    #[test]
+    fn overrides_follow_apps_to_new_indices_after_a_restart() {
+        let sink = |index: u32, name: &str| Sink {
+            index,
+            name: name.to_string(),
+            description: name.to_string(),
+            volume: String::new(),
+            sample_spec: String::new(),
+            monitor_source: None,
+        };
+        let before = [sink(55, "speakers"), sink(56, "headset")];
+        assert!(!server_restarted(&before, &before));
+        assert!(server_restarted(&before, &[sink(40, "speakers"), sink(41, "headset")]));
+        assert!(server_restarted(&before, &[]));
+        assert!(!server_restarted(&[], &before));
+        // A device coming or going is not a restart.
+        assert!(!server_restarted(&before, &[sink(55, "speakers")]));
+
+        let restore = |app: &str, volume: f32| PendingRestore {
+            app: app.to_string(),
+            volume: Some(volume),
+            muted: false,
+        };
+        let mut pending = vec![restore("Firefox", 30.0), restore("Firefox", 60.0), restore("mpv", 80.0)];
+        // Firefox used to be 101 and 102; after the restart it comes back as 7,
+        // with an unrelated stream on the index mpv had.
+        let app = |name: &str| -> Props { HashMap::from([("application.name".to_string(), name.to_string())]) };
+        let apps = HashMap::from([(7, app("Firefox")), (103, app("Discord"))]);
+
+        let matched = match_restores(&mut pending, &apps, &[7, 103]);
+        assert_eq!(matched, [(7, restore("Firefox", 30.0))]);
+        assert_eq!(pending, [restore("Firefox", 60.0), restore("mpv", 80.0)]);
+
+        let apps = HashMap::from([(8, app("mpv")), (9, app("Firefox"))]);
+        let matched = match_restores(&mut pending, &apps, &[8, 9]);
+        assert_eq!(matched, [(8, restore("mpv", 80.0)), (9, restore("Firefox", 60.0))]);
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn session_overrides_win_over_saved_and_live_values() {
+        let sinks = [Sink {
+            index: 55,
+            name: "speakers".to_string(),
+            description: "Speakers".to_string(),
+            volume: String::new(),
+            sample_spec: String::new(),
+            monitor_source: None,
+        }];
+        let mut config = Config::default();
+        config.set_app_volume("Firefox", Some(40.0));
+        config.set_app_volume("mpv", Some(50.0));
+
+        let stream = |app: &str, volume: f32, muted: bool| -> Props {
+            let volume = format!("front-left: 0 / {0}% / 0 dB,   front-right: 0 / {0}% / 0 dB", volume);
+            let mute = if muted { "yes" } else { "no" };
+            [("application.name", app), ("Sink", "55"), ("Volume", &volume), ("Mute", mute)]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        // One snapshot through the layers, as `merge_stream_state` does.
+        let mut seen = HashMap::new();
+        let mut merge = |apps: HashMap<u32, Props>, first: bool, overrides: HashMap<u32, SessionOverride>| {
+            let live_volumes = apps
+                .iter()
+                .filter_map(|(pid, p)| Some((*pid, parse_volume_percent(p.get("Volume")?, ChannelMode::Average)?)))
+                .collect();
+            let live_muted = apps.iter().map(|(pid, p)| (*pid, p["Mute"] == "yes")).collect();
+            let saved = saved_volume_changes(&apps, &sinks, &mut seen, &config, first).into_iter().collect();
+            merge_layers(&live_volumes, &live_muted, &saved, &overrides)
+        };
+        let queued = |pid: u32, volume: f32| {
+            HashMap::from([(pid, SessionOverride { volume: Some(volume), muted: None })])
+        };
+
+        // Playing before the mixer started: the saved volume is left alone.
+        let (volumes, muted) = merge(HashMap::from([(10, stream("Firefox", 65.0, false))]), true, HashMap::new());
+        assert_eq!((volumes[&10], muted[&10]), (65.0, false));
+
+        // The user drags to 30%; the command is still queued when a snapshot
+        // with the old value comes in.
+        let (volumes, _) = merge(HashMap::from([(10, stream("Firefox", 65.0, false))]), false, queued(10, 30.0));
+        assert_eq!(volumes[&10], 30.0);
+        // Sent, and reported back.
+        let (volumes, _) = merge(HashMap::from([(10, stream("Firefox", 30.0, false))]), false, HashMap::new());
+        assert_eq!(volumes[&10], 30.0);
+
+        // Another program changes it: with nothing pending, live wins.
+        let (volumes, muted) = merge(HashMap::from([(10, stream("Firefox", 80.0, true))]), false, HashMap::new());
+        assert_eq!((volumes[&10], muted[&10]), (80.0, true));
+        // An external change racing a queued user change loses to it.
+        let (volumes, muted) = merge(HashMap::from([(10, stream("Firefox", 90.0, true))]), false, queued(10, 50.0));
+        assert_eq!((volumes[&10], muted[&10]), (50.0, true));
+
+        // The stream goes away; nothing is kept for it, even an override.
+        let (volumes, muted) = merge(HashMap::new(), false, queued(10, 50.0));
+        assert!(volumes.is_empty() && muted.is_empty());
+
+        // It comes back (same index): a new stream, so its saved volume wins
+        // over live. A change restored after a restart wins over both.
+        let restored = HashMap::from([(11, SessionOverride { volume: Some(20.0), muted: Some(true) })]);
+        let apps = HashMap::from([(10, stream("Firefox", 100.0, false)), (11, stream("mpv", 100.0, false))]);
+        let (volumes, muted) = merge(apps, false, restored);
+        assert_eq!((volumes[&10], muted[&10]), (40.0, false));
+        assert_eq!((volumes[&11], muted[&11]), (20.0, true));
+
+        // Saved volumes only apply once; later external changes show as they are.
+        let (volumes, _) = merge(HashMap::from([(10, stream("Firefox", 70.0, false))]), false, HashMap::new());
+        assert_eq!(volumes[&10], 70.0);
+    }
+
+    #[test]
+    fn dry_run_starts_no_commands_from_any_thread() {
+        exec::set_dry_run(true);
+        let started = exec::STARTED.load(Ordering::Relaxed);
+        // Like the route-move thread: setters called off the UI thread.
+        std::thread::spawn(|| {
+            assert_eq!(set_app_volume(1, 50.0), Err(pactl::VolumeError::DryRun));
+            assert_eq!(pactl::move_sink_input(1, "elsewhere"), Err(pactl::VolumeError::DryRun));
+            assert_eq!(pactl::force_quantum(Some(1024)), Err(pactl::VolumeError::DryRun));
+            let module = pactl::load_module("module-null-sink", &["sink_name=x".to_string()]);
+            assert_eq!(module, Err(pactl::VolumeError::DryRun));
+            assert_eq!(mpris::call_play_pause("org.mpris.MediaPlayer2.mpv"), Err(pactl::VolumeError::DryRun));
+        })
+        .join()
+        .unwrap();
+        // A new stream fires the hook command.
+        let marker = std::env::temp_dir().join(format!("k_volume_mixer_dry_run_{}", std::process::id()));
+        let command = format!("touch '{}'", marker.display());
+        let mut hooks = Hooks::new();
+        let stream = StreamLevel { name: "mpv".to_string(), volume: Some(50.0), muted: false };
+        hooks.observe(&command, None, Levels::default());
+        hooks.observe(&command, None, Levels { system: None, streams: HashMap::from([(1, stream)]) });
+        exec::set_dry_run(false);
+
+        assert_eq!(exec::STARTED.load(Ordering::Relaxed), started);
+        thread::sleep(Duration::from_millis(200));
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn tray_status_line() {
+        assert_eq!(tray_status(Some(40.0), false, 2), "40% · 2 apps playing");
+        assert_eq!(tray_status(Some(39.6), true, 1), "Muted (40%) · 1 app playing");
+        assert_eq!(tray_status(None, false, 0), "No output device · nothing playing");
+    }
+
+    #[test]
+    fn inactive_reasons() {
+        let stream = |corked: &str, volume: &str| -> Props {
+            [("Corked", corked), ("Volume", volume)]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let volume = "front-left: 42597 /  65% / -11.23 dB,   front-right: 42597 /  65% / -11.23 dB";
+
+        assert_eq!(inactive_reason(&stream("yes", volume), Some(0.0)).map(|r| r.0), Some("⏸ Paused"));
+        assert_eq!(inactive_reason(&stream("no", "(invalid)"), None).map(|r| r.0), Some("No volume"));
+        assert_eq!(inactive_reason(&stream("no", volume), Some(0.001)).map(|r| r.0), Some("💤 Idle"));
+        assert_eq!(inactive_reason(&stream("no", volume), Some(0.3)), None);
+        assert_eq!(inactive_reason(&stream("no", volume), None), None);
+    }
 }
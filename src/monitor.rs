@@ -0,0 +1,175 @@
+//! `k_volume_mixer monitor`: print one JSON object per line for every volume,
+//! mute or stream change, driven by `pactl subscribe`.
+//!
+//! ```text
+//! {"event":"volume","target":"system","value":40.00}
+//! {"event":"stream-new","target":"sink-input:42","name":"Firefox","value":65.00}
+//! {"event":"mute","target":"sink-input:42","name":"Firefox","value":true}
+//! {"event":"stream-remove","target":"sink-input:42","name":"Firefox","value":null}
+//! ```
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+use crate::config::Config;
+use crate::pactl::{self, ChannelMode, EventKind, VolumeError};
+
+#[derive(Clone, PartialEq)]
+struct Stream {
+    name: String,
+    volume: Option<f32>,
+    muted: bool,
+}
+
+struct State {
+    system: Option<(f32, bool)>,
+    streams: HashMap<u32, Stream>,
+}
+
+impl State {
+    fn capture(label_properties: &[String]) -> Result<Self, VolumeError> {
+        let streams = pactl::parse_sink_inputs()?
+            .into_iter()
+            .map(|(index, props)| {
+                let stream = Stream {
+                    name: pactl::resolve_display_name(&props, label_properties).to_string(),
+                    volume: props
+                        .get("Volume")
+                        .and_then(|v| pactl::parse_volume_percent(v, ChannelMode::default())),
+                    muted: props.get("Mute").is_some_and(|m| m == "yes"),
+                };
+                (index, stream)
+            })
+            .collect();
+
+        Ok(Self {
+            system: pactl::get_system_state().ok(),
+            streams,
+        })
+    }
+}
+
+/// Run until `pactl subscribe` exits or stdout is closed (e.g. `| head`).
+pub fn run() -> io::Result<()> {
+    let mut child = Command::new("pactl")
+        .arg("subscribe")
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let events = BufReader::new(child.stdout.take().expect("stdout is piped"));
+
+    let result = stream_events(events);
+    let _ = child.kill();
+    let _ = child.wait();
+
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        other => other,
+    }
+}
+
+fn stream_events(events: impl BufRead) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    let label_properties = Config::load().label_properties;
+    let mut state = State::capture(&label_properties).map_err(io::Error::other)?;
+
+    for line in events.lines() {
+        let Some(event) = pactl::parse_subscribe_line(&line?) else {
+            continue;
+        };
+
+        match (event.facility.as_str(), event.kind) {
+            ("sink-input", EventKind::Remove) => {
+                if let Some(stream) = state.streams.remove(&event.index) {
+                    emit_stream(&mut out, "stream-remove", event.index, &stream.name, "null")?;
+                }
+            }
+            ("sink-input" | "sink" | "server", _) => {
+                // On a failed read, wait for the next event rather than
+                // reporting every stream as gone.
+                match State::capture(&label_properties) {
+                    Ok(next) => {
+                        emit_diff(&mut out, &state, &next)?;
+                        state = next;
+                    }
+                    Err(e) => eprintln!("monitor: {}", e),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn emit_diff(out: &mut impl Write, prev: &State, next: &State) -> io::Result<()> {
+    if let Some((vol, muted)) = next.system {
+        let (prev_vol, prev_muted) = prev.system.unzip();
+        if prev_vol != Some(vol) {
+            emit(out, "volume", "system", None, &format!("{:.2}", vol))?;
+        }
+        if prev_muted != Some(muted) {
+            emit(out, "mute", "system", None, &muted.to_string())?;
+        }
+    }
+
+    for (index, stream) in &next.streams {
+        match prev.streams.get(index) {
+            None => emit_stream(out, "stream-new", *index, &stream.name, &volume_json(stream.volume))?,
+            Some(old) if old == stream => {}
+            Some(old) => {
+                if old.volume != stream.volume {
+                    emit_stream(out, "volume", *index, &stream.name, &volume_json(stream.volume))?;
+                }
+                if old.muted != stream.muted {
+                    emit_stream(out, "mute", *index, &stream.name, &stream.muted.to_string())?;
+                }
+            }
+        }
+    }
+
+    for (index, stream) in &prev.streams {
+        if !next.streams.contains_key(index) {
+            emit_stream(out, "stream-remove", *index, &stream.name, "null")?;
+        }
+    }
+
+    Ok(())
+}
+
+fn emit_stream(out: &mut impl Write, event: &str, index: u32, name: &str, value: &str) -> io::Result<()> {
+    emit(out, event, &format!("sink-input:{}", index), Some(name), value)
+}
+
+/// Write one JSON line. `value` must already be valid JSON.
+fn emit(out: &mut impl Write, event: &str, target: &str, name: Option<&str>, value: &str) -> io::Result<()> {
+    let name = name
+        .map(|n| format!(",\"name\":\"{}\"", json_escape(n)))
+        .unwrap_or_default();
+    writeln!(
+        out,
+        "{{\"event\":\"{}\",\"target\":\"{}\"{},\"value\":{}}}",
+        event, target, name, value
+    )?;
+    out.flush()
+}
+
+fn volume_json(volume: Option<f32>) -> String {
+    volume.map_or_else(|| "null".to_string(), |v| format!("{:.2}", v))
+}
+
+fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
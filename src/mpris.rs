@@ -0,0 +1,164 @@
+//! Media players on the session bus (MPRIS), matched to streams so an app's
+//! group can offer play/pause. D-Bus is spoken through `gdbus`, as in
+//! `accent`, started by `exec` so that dry runs don't pause anything.
+//!
+//! A background thread lists the `org.mpris.MediaPlayer2.*` names every
+//! `POLL_INTERVAL` and asks the bus which process owns each. Without `gdbus`
+//! or a session bus there are simply no players.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::exec;
+use crate::pactl::VolumeError;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+#[derive(Clone, Debug, PartialEq)]
+struct Player {
+    bus_name: String, // org.mpris.MediaPlayer2.firefox.instance_1_45
+    process_id: Option<u32>,
+}
+
+pub struct Players {
+    players: Arc<Mutex<Vec<Player>>>,
+}
+
+impl Players {
+    pub fn new() -> Self {
+        let players = Arc::new(Mutex::new(Vec::new()));
+        {
+            let players = Arc::clone(&players);
+            // Stops without gdbus or a session bus.
+            thread::spawn(move || {
+                while let Some(found) = list_players() {
+                    match players.lock() {
+                        Ok(mut players) => *players = found,
+                        Err(_) => break,
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+            });
+        }
+        Self { players }
+    }
+
+    /// Bus name of the player behind a stream: the one owned by its process,
+    /// else one named after the app (`names`, e.g. its binary).
+    pub fn find(&self, process_id: Option<u32>, names: &[&str]) -> Option<String> {
+        let players = self.players.lock().ok()?;
+        find_player(&players, process_id, names).map(|p| p.bus_name.clone())
+    }
+}
+
+/// Toggle playback of player `bus_name` on the command worker, off the UI
+/// thread.
+pub fn play_pause(bus_name: &str) {
+    let bus_name = bus_name.to_string();
+    exec::post(None, move || match call_play_pause(&bus_name) {
+        Ok(()) | Err(VolumeError::DryRun) => {}
+        Err(e) => eprintln!("play/pause of {}: {}", bus_name, e),
+    });
+}
+
+/// `play_pause` on the calling thread.
+pub fn call_play_pause(bus_name: &str) -> Result<(), VolumeError> {
+    exec::run(
+        "gdbus",
+        &[
+            "call",
+            "--session",
+            "--timeout",
+            "2",
+            "--dest",
+            bus_name,
+            "--object-path",
+            "/org/mpris/MediaPlayer2",
+            "--method",
+            "org.mpris.MediaPlayer2.Player.PlayPause",
+        ],
+    )
+    .map(drop)
+}
+
+fn list_players() -> Option<Vec<Player>> {
+    let names = bus_call(&["--method", "org.freedesktop.DBus.ListNames"])?;
+    let players = parse_names(&names)
+        .into_iter()
+        .filter(|name| name.starts_with(MPRIS_PREFIX))
+        .map(|bus_name| {
+            let process_id = bus_call(&["--method", "org.freedesktop.DBus.GetConnectionUnixProcessID", &bus_name])
+                .and_then(|reply| parse_process_id(&reply));
+            Player { bus_name, process_id }
+        })
+        .collect();
+    Some(players)
+}
+
+/// A method call on the bus daemon itself, returning the reply text.
+fn bus_call(method: &[&str]) -> Option<String> {
+    let mut args = vec![
+        "call",
+        "--session",
+        "--timeout",
+        "2",
+        "--dest",
+        "org.freedesktop.DBus",
+        "--object-path",
+        "/org/freedesktop/DBus",
+    ];
+    args.extend_from_slice(method);
+    let output = exec::read("gdbus", &args).ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The names in a `ListNames` reply such as `(['org.freedesktop.DBus', ':1.4'],)`.
+fn parse_names(text: &str) -> Vec<String> {
+    text.split('\'').skip(1).step_by(2).map(str::to_string).collect()
+}
+
+/// The number in a `GetConnectionUnixProcessID` reply such as `(uint32 4242,)`.
+fn parse_process_id(text: &str) -> Option<u32> {
+    text.split_whitespace().nth(1)?.trim_end_matches([',', ')']).parse().ok()
+}
+
+fn find_player<'a>(players: &'a [Player], process_id: Option<u32>, names: &[&str]) -> Option<&'a Player> {
+    let by_process = process_id.and_then(|id| players.iter().find(|p| p.process_id == Some(id)));
+    by_process.or_else(|| {
+        players.iter().find(|p| {
+            // The part after the prefix, without any `.instance…` suffix.
+            let player = p.bus_name.trim_start_matches(MPRIS_PREFIX).split('.').next().unwrap_or_default();
+            names.iter().any(|name| name.eq_ignore_ascii_case(player))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bus_replies() {
+        let names = parse_names("(['org.freedesktop.DBus', ':1.4', 'org.mpris.MediaPlayer2.spotify'],)\n");
+        assert_eq!(names, ["org.freedesktop.DBus", ":1.4", "org.mpris.MediaPlayer2.spotify"]);
+        assert_eq!(parse_process_id("(uint32 4242,)\n"), Some(4242));
+        assert_eq!(parse_process_id("()"), None);
+    }
+
+    #[test]
+    fn matches_players_by_process_then_name() {
+        let player = |bus_name: &str, process_id| Player {
+            bus_name: format!("{}{}", MPRIS_PREFIX, bus_name),
+            process_id,
+        };
+        let players = [player("firefox.instance_1_45", Some(4242)), player("spotify", Some(777))];
+
+        let found = |process_id, names: &[&str]| find_player(&players, process_id, names).map(|p| p.process_id);
+        assert_eq!(found(Some(777), &["Spotify"]), Some(Some(777)));
+        // Audio from another process of the app (a content or audio process).
+        assert_eq!(found(Some(5000), &["Firefox", "firefox"]), Some(Some(4242)));
+        assert_eq!(found(None, &["mpv"]), None);
+    }
+}
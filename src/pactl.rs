@@ -0,0 +1,1213 @@
+//! Thin wrappers around `pactl`/`wpctl`/`pw-metadata` and parsers for their output.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::exec;
+
+/// Properties of one stream (or device), keyed by pactl property name plus the
+/// synthetic `Name`, `Description`, `Sink`/`Source`, `Volume` and `Mute` entries.
+pub type Props = HashMap<String, String>;
+
+/// Prefix of every sink the mixer itself creates (equalizers, broadcasts).
+pub const OWN_SINK_PREFIX: &str = "k_volume_mixer_";
+
+/// Send whole percentages only (see `set_integer_volumes`).
+static INTEGER_VOLUMES: AtomicBool = AtomicBool::new(false);
+
+/// Why talking to the sound server failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VolumeError {
+    /// The program (`pactl`, `wpctl`, ...) isn't installed.
+    BinaryNotFound(String),
+    /// It ran but exited unsuccessfully.
+    CommandFailed { code: Option<i32>, stderr: String },
+    /// It answered with something we couldn't read.
+    ParseError(String),
+    /// It didn't answer in time, so it was killed.
+    Timeout,
+    /// There is no default output to ask about.
+    NoDefaultSink,
+    /// Not run at all: dry-run mode.
+    DryRun,
+}
+
+impl fmt::Display for VolumeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VolumeError::BinaryNotFound(program) => write!(f, "{} isn't installed", program),
+            VolumeError::CommandFailed { code, stderr } => {
+                match code {
+                    Some(code) => write!(f, "The sound server refused (exit code {})", code)?,
+                    None => write!(f, "The sound server command was killed")?,
+                }
+                match stderr.lines().next().filter(|line| !line.trim().is_empty()) {
+                    Some(line) => write!(f, ": {}", line.trim()),
+                    None => Ok(()),
+                }
+            }
+            VolumeError::ParseError(text) => write!(f, "Couldn't read the sound server's answer: {:?}", text),
+            VolumeError::Timeout => write!(f, "The sound server didn't answer in time"),
+            VolumeError::NoDefaultSink => write!(f, "There is no default output device"),
+            VolumeError::DryRun => write!(f, "Not run (dry run)"),
+        }
+    }
+}
+
+impl std::error::Error for VolumeError {}
+
+/// Run a command that changes something on the sound server (see
+/// `exec::run`). Every setter goes through here, whichever thread it is
+/// called from; the caller decides how to show a failure.
+fn change(program: &str, args: &[&str]) -> Result<(), VolumeError> {
+    let result = exec::run(program, args).map(drop);
+    if let Err(e) = &result
+        && *e != VolumeError::DryRun
+    {
+        eprintln!("{} {}: {}", program, args.join(" "), e);
+    }
+    result
+}
+
+/// Round every volume sent from now on to a whole percent, or keep sending
+/// fractional ones (two decimals).
+pub fn set_integer_volumes(integer: bool) {
+    INTEGER_VOLUMES.store(integer, Ordering::Relaxed);
+}
+
+/// A volume argument such as `42.50%` (or `43%` with integer volumes on).
+fn percent_arg(vol: f32) -> String {
+    if INTEGER_VOLUMES.load(Ordering::Relaxed) {
+        format!("{:.0}%", vol.round())
+    } else {
+        format!("{:.2}%", vol)
+    }
+}
+
+pub fn set_main_volume(vol: f32) -> Result<(), VolumeError> {
+    change("wpctl", &["set-volume", "@DEFAULT_AUDIO_SINK@", &percent_arg(vol)])
+}
+
+pub fn set_main_mute(muted: bool) -> Result<(), VolumeError> {
+    change("wpctl", &["set-mute", "@DEFAULT_AUDIO_SINK@", if muted { "1" } else { "0" }])
+}
+
+pub fn toggle_main_mute() -> Result<(), VolumeError> {
+    change("wpctl", &["set-mute", "@DEFAULT_AUDIO_SINK@", "toggle"])
+}
+
+/// Change the system volume by `delta` percent relative to where it is,
+/// capped at 100%.
+pub fn step_main_volume(delta: f32) -> Result<(), VolumeError> {
+    let step = if delta >= 0.0 {
+        format!("{}+", percent_arg(delta))
+    } else {
+        format!("{}-", percent_arg(-delta))
+    };
+    change("wpctl", &["set-volume", "-l", "1.0", "@DEFAULT_AUDIO_SINK@", &step])
+}
+
+pub fn set_app_volume(index: u32, vol: f32) -> Result<(), VolumeError> {
+    change("pactl", &["set-sink-input-volume", &index.to_string(), &percent_arg(vol)])
+}
+
+pub fn set_app_mute(index: u32, muted: bool) -> Result<(), VolumeError> {
+    change("pactl", &["set-sink-input-mute", &index.to_string(), if muted { "1" } else { "0" }])
+}
+
+pub fn set_mic_volume(vol: f32) -> Result<(), VolumeError> {
+    change("wpctl", &["set-volume", "@DEFAULT_AUDIO_SOURCE@", &percent_arg(vol)])
+}
+
+pub fn set_mic_mute(muted: bool) -> Result<(), VolumeError> {
+    change("wpctl", &["set-mute", "@DEFAULT_AUDIO_SOURCE@", if muted { "1" } else { "0" }])
+}
+
+pub fn set_recording_volume(index: u32, vol: f32) -> Result<(), VolumeError> {
+    change("pactl", &["set-source-output-volume", &index.to_string(), &percent_arg(vol)])
+}
+
+/// System volume in percent and whether the default sink is muted.
+pub fn get_system_state() -> Result<(f32, bool), VolumeError> {
+    get_node_state("@DEFAULT_AUDIO_SINK@")
+}
+
+/// Microphone (default source) volume in percent and whether it's muted.
+pub fn get_mic_state() -> Result<(f32, bool), VolumeError> {
+    get_node_state("@DEFAULT_AUDIO_SOURCE@")
+}
+
+fn get_node_state(node: &str) -> Result<(f32, bool), VolumeError> {
+    // wpctl missing: see `BackendMode::NoWpctl`
+    node_state(node, exec::read("wpctl", &["get-volume", node]))
+}
+
+/// Volume and mute from `wpctl get-volume node`'s outcome. wpctl says the
+/// default node is "not found" when there is none.
+fn node_state(node: &str, output: Result<Output, VolumeError>) -> Result<(f32, bool), VolumeError> {
+    let output = match output {
+        Err(VolumeError::CommandFailed { stderr, .. })
+            if node == "@DEFAULT_AUDIO_SINK@" && stderr.contains("not found") =>
+        {
+            return Err(VolumeError::NoDefaultSink);
+        }
+        output => output?,
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_wpctl_volume(&stdout).ok_or_else(|| VolumeError::ParseError(stdout.trim().to_string()))
+}
+
+/// Parse `wpctl get-volume` output such as `Volume: 0.40 [MUTED]`.
+pub fn parse_wpctl_volume(stdout: &str) -> Option<(f32, bool)> {
+    let mut parts = stdout.split_whitespace();
+    let volume = parts.nth(1)?.parse::<f32>().ok()?;
+    let muted = parts.any(|p| p == "[MUTED]");
+    Some((volume * 100.0, muted)) // as percentage
+}
+
+/// An audio device node from `wpctl status`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    /// PipeWire object id, as accepted by `wpctl set-volume` and friends.
+    pub id: u32,
+    pub name: String,
+    /// Percent, when wpctl shows one.
+    pub volume: Option<f32>,
+    pub muted: bool,
+    /// Marked `*`: the default sink or source.
+    pub is_default: bool,
+}
+
+/// The audio sinks and sources `wpctl status` lists.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WpctlStatus {
+    pub sinks: Vec<Node>,
+    pub sources: Vec<Node>,
+}
+
+impl WpctlStatus {
+    pub fn default_source(&self) -> Option<&Node> {
+        self.sources.iter().find(|n| n.is_default)
+    }
+}
+
+pub fn wpctl_status() -> Result<WpctlStatus, VolumeError> {
+    let output = exec::read("wpctl", &["status"])?;
+    Ok(parse_wpctl_status(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse the tree `wpctl status` prints. Only the `Audio` section's `Sinks:`
+/// and `Sources:` are read; everything else (clients, streams, video, ...) is
+/// skipped. Lines look like
+///
+/// ```text
+/// Audio
+///  ├─ Sinks:
+///  │  *   47. Built-in Audio Analog Stereo        [vol: 0.40]
+///  │      58. HDMI Output                         [vol: 1.00 MUTED]
+/// ```
+pub fn parse_wpctl_status(stdout: &str) -> WpctlStatus {
+    let mut status = WpctlStatus::default();
+    let mut in_audio = false;
+    let mut list: Option<&mut Vec<Node>> = None;
+
+    for line in stdout.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        // Top-level sections ("Audio", "Video", "Settings", ...) start at column 0.
+        if !line.starts_with(|c: char| c.is_whitespace() || is_tree_char(c)) {
+            in_audio = line.trim() == "Audio";
+            list = None;
+            continue;
+        }
+        if !in_audio {
+            continue;
+        }
+
+        let text = line.trim_start_matches(|c: char| c.is_whitespace() || is_tree_char(c));
+        if let Some(heading) = text.strip_suffix(':') {
+            list = match heading {
+                "Sinks" => Some(&mut status.sinks),
+                "Sources" => Some(&mut status.sources),
+                _ => None,
+            };
+            continue;
+        }
+        if let Some(list) = &mut list
+            && let Some(node) = parse_status_node(text)
+        {
+            list.push(node);
+        }
+    }
+    status
+}
+
+fn is_tree_char(c: char) -> bool {
+    matches!(c, '│' | '├' | '└' | '─')
+}
+
+/// `*   47. Built-in Audio Analog Stereo   [vol: 0.40 MUTED]`, tree already stripped.
+fn parse_status_node(text: &str) -> Option<Node> {
+    let (is_default, text) = match text.strip_prefix('*') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, text),
+    };
+    let (id, rest) = text.split_once(". ")?;
+    let id = id.parse().ok()?;
+
+    let (name, volume, muted) = match rest.rfind("[vol:") {
+        Some(start) => {
+            let mut parts = rest[start + "[vol:".len()..].trim_end_matches(']').split_whitespace();
+            let volume = parts.next().and_then(|v| v.parse::<f32>().ok()).map(|v| v * 100.0);
+            (&rest[..start], volume, parts.any(|p| p == "MUTED"))
+        }
+        None => (rest, None, false),
+    };
+    Some(Node {
+        id,
+        name: name.trim().to_string(),
+        volume,
+        muted,
+        is_default,
+    })
+}
+
+/// How a multi-channel volume is collapsed into the single value a slider shows.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChannelMode {
+    #[default]
+    Average,
+    Max,
+    First,
+}
+
+impl ChannelMode {
+    pub const ALL: [ChannelMode; 3] = [ChannelMode::Average, ChannelMode::Max, ChannelMode::First];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ChannelMode::Average => "Average of channels",
+            ChannelMode::Max => "Loudest channel",
+            ChannelMode::First => "First channel",
+        }
+    }
+}
+
+/// Extract a percentage from a pactl `Volume:` value such as
+/// `front-left: 65536 / 100% / 0.00 dB,   front-right: ...`, combining the
+/// channels according to `mode`.
+pub fn parse_volume_percent(vol_str: &str, mode: ChannelMode) -> Option<f32> {
+    let mut channels = vol_str.split(',').filter_map(|channel| {
+        let percent = channel.split('/').nth(1)?;
+        percent.trim().strip_suffix('%')?.trim().parse::<f32>().ok()
+    });
+
+    match mode {
+        ChannelMode::First => channels.next(),
+        ChannelMode::Max => channels.reduce(f32::max),
+        ChannelMode::Average => {
+            let (sum, count) = channels.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+            (count > 0).then(|| sum / count as f32)
+        }
+    }
+}
+
+/// One channel of a pactl `Volume:` value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelVolume {
+    pub channel: String,
+    /// Out of 65536 for 100%.
+    pub raw: u32,
+    pub percent: f32,
+    pub db: f32, // -inf when silent
+}
+
+/// Every channel of a pactl `Volume:` value, e.g.
+/// `front-left: 42597 /  65% / -11.23 dB,   front-right: ...` or
+/// `mono: 65536 / 100% / 0.00 dB`.
+pub fn parse_channel_volumes(vol_str: &str) -> Vec<ChannelVolume> {
+    vol_str
+        .split(',')
+        .filter_map(|channel| {
+            let (name, values) = channel.split_once(':')?;
+            let mut values = values.split('/').map(str::trim);
+            Some(ChannelVolume {
+                channel: name.trim().to_string(),
+                raw: values.next()?.parse().ok()?,
+                percent: values.next()?.strip_suffix('%')?.trim().parse().ok()?,
+                db: values.next()?.strip_suffix("dB")?.trim().parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+pub fn parse_sink_inputs() -> Result<HashMap<u32, Props>, VolumeError> {
+    let output = exec::read("pactl", &["list", "sink-inputs"])?;
+    let mut streams = parse_sink_inputs_output(&String::from_utf8_lossy(&output.stdout));
+    name_nameless_streams(&mut streams);
+    Ok(streams)
+}
+
+/// Recording streams (`pactl list source-outputs`), same shape as sink inputs
+/// with `Source` in place of `Sink`.
+pub fn parse_source_outputs() -> Result<HashMap<u32, Props>, VolumeError> {
+    let output = exec::read("pactl", &["list", "source-outputs"])?;
+    let mut streams = parse_list(&String::from_utf8_lossy(&output.stdout), "Source Output #");
+    name_nameless_streams(&mut streams);
+    Ok(streams)
+}
+
+/// Give streams without any of the usual name properties (nor a node name)
+/// something better than "Unknown": their client's name (as `client.name`, see
+/// `resolve_display_name`) and the binary of their process, read from /proc.
+fn name_nameless_streams(streams: &mut HashMap<u32, Props>) {
+    if streams.values().all(has_usual_name) {
+        return;
+    }
+    let clients = exec::read("pactl", &["list", "clients"])
+        .map(|output| parse_list(&String::from_utf8_lossy(&output.stdout), "Client #"))
+        .unwrap_or_default();
+    fill_missing_names(streams, &clients, process_binary);
+}
+
+/// `name_nameless_streams` without the I/O: `clients` is `pactl list
+/// clients` by index, `binary_of` maps a process id to its binary name.
+fn fill_missing_names(
+    streams: &mut HashMap<u32, Props>,
+    clients: &HashMap<u32, Props>,
+    binary_of: impl Fn(u32) -> Option<String>,
+) {
+    for props in streams.values_mut() {
+        if has_usual_name(props) {
+            continue;
+        }
+        let client = props.get("Client").and_then(|c| c.parse::<u32>().ok()).and_then(|c| clients.get(&c));
+        if let Some(name) = client.and_then(|c| c.get("application.name")) {
+            props.insert("client.name".to_string(), name.clone());
+        }
+        let pid = process_id(props).or_else(|| client.and_then(process_id));
+        if let Some(binary) = pid.and_then(&binary_of) {
+            props.insert("application.process.binary".to_string(), binary);
+        }
+    }
+}
+
+/// Whether `resolve_display_name` finds a name without the client or /proc:
+/// one of the default label properties, or the PipeWire node name.
+fn has_usual_name(props: &Props) -> bool {
+    DEFAULT_LABEL_PROPERTIES
+        .iter()
+        .chain(&["node.name"])
+        .any(|key| props.get(*key).is_some_and(|v| !v.is_empty()))
+}
+
+/// Basename of a process's executable, or its `comm` when the link can't be
+/// read (another user's process).
+fn process_binary(pid: u32) -> Option<String> {
+    let exe = std::fs::read_link(format!("/proc/{}/exe", pid)).ok();
+    let name = match exe.as_ref().and_then(|exe| exe.file_name()) {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?.trim().to_string(),
+    };
+    (!name.is_empty()).then_some(name)
+}
+
+/// Parse the output of `pactl list sink-inputs`.
+pub fn parse_sink_inputs_output(stdout: &str) -> HashMap<u32, Props> {
+    parse_list(stdout, "Sink Input #")
+}
+
+/// `Field: value` lines kept from a `pactl list` entry, besides its properties.
+const LIST_FIELDS: [&str; 12] = [
+    "Name",
+    "Description",
+    "Sink",
+    "Source",
+    "Client",
+    "Volume",
+    "Mute",
+    "Corked",
+    "Sample Specification",
+    "Channel Map",
+    "Active Profile",
+    "Monitor Source",
+];
+
+/// Parse a `pactl list` stream listing whose entries start with `header`
+/// followed by the index.
+fn parse_list(stdout: &str, header: &str) -> HashMap<u32, Props> {
+    let mut result: HashMap<u32, Props> = HashMap::new();
+    // The entry being filled in; moved into `result` when the next one starts.
+    let mut current: Option<(u32, Props)> = None;
+
+    for line in stdout.lines() {
+        let trimmed = line.trim_start();
+
+        if let Some(id_str) = trimmed.strip_prefix(header)
+            && let Ok(id) = id_str.trim().parse::<u32>()
+        {
+            // Streams carry a few dozen properties; skip most rehashing.
+            if let Some((id, props)) = current.replace((id, Props::with_capacity(64))) {
+                result.insert(id, props);
+            }
+            continue;
+        }
+        let Some((_, props)) = &mut current else {
+            continue;
+        };
+
+        if let Some((field, value)) = trimmed.split_once(':')
+            && LIST_FIELDS.contains(&field)
+        {
+            props.insert(field.to_string(), value.trim().to_string());
+        } else if let Some((key, value)) = trimmed.split_once(" = ") {
+            props.insert(key.to_string(), value.trim_matches('"').to_string());
+        }
+    }
+
+    if let Some((id, props)) = current {
+        result.insert(id, props);
+    }
+    result
+}
+
+/// How much of the mixer works against the sound server it found. Anything
+/// but `Full` is shown as a badge explaining what is missing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BackendMode {
+    Full,
+    /// `wpctl` isn't installed (e.g. plain PulseAudio).
+    NoWpctl,
+    /// `PULSE_SERVER` points at another machine or socket.
+    Remote(String),
+    /// `pactl` gets no answer from the server, e.g. while it restarts.
+    Unreachable,
+}
+
+impl BackendMode {
+    /// The most limiting mode that applies.
+    pub fn detect(reachable: bool, pulse_server: Option<&str>, wpctl: bool) -> Self {
+        match pulse_server {
+            _ if !reachable => Self::Unreachable,
+            Some(server) => Self::Remote(server.to_string()),
+            None if !wpctl => Self::NoWpctl,
+            None => Self::Full,
+        }
+    }
+
+    /// Badge text and a tooltip on what doesn't work, unless everything does.
+    pub fn badge(&self) -> Option<(String, String)> {
+        let (label, detail) = match self {
+            Self::Full => return None,
+            Self::NoWpctl => (
+                "⚠ pactl only".to_string(),
+                "wpctl (WirePlumber) was not found. The system and microphone sliders, \
+                 per-app latency, media roles and the global quantum don't work; app volumes do."
+                    .to_string(),
+            ),
+            Self::Remote(server) => (
+                "🖧 Remote server".to_string(),
+                format!(
+                    "Controlling the server at {} (PULSE_SERVER). The system and microphone sliders and \
+                     PipeWire-only controls act on this machine's own server, if any, not that one.",
+                    server
+                ),
+            ),
+            Self::Unreachable => (
+                "⟳ Reconnecting".to_string(),
+                "The sound server isn't answering, e.g. while it restarts. The lists are empty until it's back; \
+                 this session's changes are put back then."
+                    .to_string(),
+            ),
+        };
+        Some((label, detail))
+    }
+}
+
+/// Whether `program` can be started at all.
+pub fn command_available(program: &str) -> bool {
+    !matches!(exec::read(program, &["--help"]), Err(VolumeError::BinaryNotFound(_)))
+}
+
+/// The server named by `PULSE_SERVER`, when set.
+pub fn pulse_server() -> Option<String> {
+    std::env::var("PULSE_SERVER").ok().filter(|s| !s.trim().is_empty())
+}
+
+/// Whether `pactl` can talk to the server.
+pub fn server_reachable() -> bool {
+    exec::read("pactl", &["info"]).is_ok()
+}
+
+/// Sound servers of other sessions that might be readable: a system-wide
+/// instance and other users' PipeWire/PulseAudio sockets. Our own is skipped.
+pub fn other_servers() -> Vec<PathBuf> {
+    let own = std::env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("pulse/native"));
+    let mut servers = vec![PathBuf::from("/run/pulse/native")];
+    if let Ok(users) = std::fs::read_dir("/run/user") {
+        servers.extend(users.flatten().map(|entry| entry.path().join("pulse/native")));
+    }
+    servers.retain(|path| path.exists() && Some(path) != own.as_ref());
+    servers.sort();
+    servers
+}
+
+/// Sink inputs of another server. Access is usually refused unless the admin
+/// allowed it, so the error is returned for display rather than logged.
+pub fn server_sink_inputs(server: &Path) -> Result<HashMap<u32, Props>, String> {
+    let server = format!("unix:{}", server.display());
+    let output = match exec::read("pactl", &["--server", &server, "list", "sink-inputs"]) {
+        Ok(output) => output,
+        Err(VolumeError::CommandFailed { stderr, .. }) => {
+            return Err(stderr.lines().last().unwrap_or("access denied").trim().to_string());
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+    Ok(parse_sink_inputs_output(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Name of the default sink, if the server has one.
+pub fn default_sink() -> Result<Option<String>, VolumeError> {
+    let output = exec::read("pactl", &["get-default-sink"])?;
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok((!name.is_empty()).then_some(name))
+}
+
+/// Ask PipeWire to run node `node_id` at `frames`/`rate` latency. Only some
+/// nodes honour this at runtime, hence "experimental" in the UI.
+pub fn set_node_latency(node_id: u32, frames: u32, rate: u32) -> Result<(), VolumeError> {
+    set_node_metadata(node_id, "node.latency", &format!("{}/{}", frames, rate))
+}
+
+/// Media roles the session manager's policy knows about.
+pub const MEDIA_ROLES: [&str; 11] = [
+    "Music",
+    "Movie",
+    "Game",
+    "Communication",
+    "Notification",
+    "Accessibility",
+    "Production",
+    "Camera",
+    "Screen",
+    "DSP",
+    "Test",
+];
+
+/// Stream properties that steer automatic routing, shown (read-only) next to
+/// the role.
+pub const ROUTING_FLAGS: [&str; 5] = [
+    "node.dont-reconnect",
+    "node.dont-move",
+    "node.autoconnect",
+    "node.passive",
+    "stream.dont-remix",
+];
+
+/// Reclassify node `node_id` under `role`, or drop the override with `None`.
+/// Like latency this goes through PipeWire metadata, which the policy may
+/// only consult when the stream is (re)linked.
+pub fn set_media_role(node_id: u32, role: Option<&str>) -> Result<(), VolumeError> {
+    match role {
+        Some(role) => set_node_metadata(node_id, "media.role", role),
+        None => change("pw-metadata", &["-d", &node_id.to_string(), "media.role"]),
+    }
+}
+
+fn set_node_metadata(node_id: u32, key: &str, value: &str) -> Result<(), VolumeError> {
+    change("pw-metadata", &[&node_id.to_string(), key, value])
+}
+
+/// The graph clock, from PipeWire's `settings` metadata.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ClockSettings {
+    pub rate: Option<u32>,
+    /// Quantum (frames per cycle) used when no stream asks for another.
+    pub quantum: Option<u32>,
+    /// Quantum forced on every stream; None (0 in the metadata) when not forced.
+    pub forced_quantum: Option<u32>,
+}
+
+/// Read the clock settings with `pw-metadata -n settings`.
+pub fn clock_settings() -> Result<ClockSettings, VolumeError> {
+    let output = exec::read("pw-metadata", &["-n", "settings"])?;
+    Ok(parse_clock_settings(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Force the graph quantum for every stream, or with `None` go back to letting
+/// streams and the default decide.
+pub fn force_quantum(quantum: Option<u32>) -> Result<(), VolumeError> {
+    let value = quantum.unwrap_or(0).to_string();
+    change("pw-metadata", &["-n", "settings", "0", "clock.force-quantum", &value])
+}
+
+/// Parse `pw-metadata` lines such as
+/// `update: id:0 key:'clock.quantum' value:'1024' type:''`.
+fn parse_clock_settings(text: &str) -> ClockSettings {
+    let mut settings = ClockSettings::default();
+    for line in text.lines() {
+        let field = |name: &str| {
+            let start = line.find(&format!("{}:'", name))? + name.len() + 2;
+            let len = line[start..].find('\'')?;
+            Some(&line[start..start + len])
+        };
+        let (Some(key), Some(value)) = (field("key"), field("value")) else {
+            continue;
+        };
+        let value = value.parse::<u32>().ok();
+        match key {
+            "clock.rate" => settings.rate = value,
+            "clock.quantum" => settings.quantum = value,
+            "clock.force-quantum" => settings.forced_quantum = value.filter(|q| *q > 0),
+            _ => {}
+        }
+    }
+    settings
+}
+
+/// Parse a `node.latency` property such as `1024/48000` into (frames, rate).
+pub fn parse_latency(value: &str) -> Option<(u32, u32)> {
+    let (frames, rate) = value.split_once('/')?;
+    let frames = frames.trim().parse().ok()?;
+    let rate = rate.trim().parse().ok()?;
+    (rate > 0).then_some((frames, rate))
+}
+
+/// Load a server module, returning its index.
+pub fn load_module(name: &str, args: &[String]) -> Result<u32, VolumeError> {
+    let mut command = vec!["load-module", name];
+    command.extend(args.iter().map(String::as_str));
+    let out = exec::run("pactl", &command)?;
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    stdout.trim().parse().map_err(|_| VolumeError::ParseError(stdout.trim().to_string()))
+}
+
+pub fn unload_module(index: u32) -> Result<(), VolumeError> {
+    change("pactl", &["unload-module", &index.to_string()])
+}
+
+pub fn move_sink_input(index: u32, sink: &str) -> Result<(), VolumeError> {
+    change("pactl", &["move-sink-input", &index.to_string(), sink])
+}
+
+/// An output device as listed by `pactl list sinks`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sink {
+    pub index: u32,
+    pub name: String,
+    pub description: String,
+    /// Raw `Volume:` value, for `parse_volume_percent`.
+    pub volume: String,
+    /// Raw `Sample Specification:` value, e.g. `s32le 2ch 48000Hz`.
+    pub sample_spec: String,
+    /// The source that monitors it (what plays on it), when it has one.
+    pub monitor_source: Option<String>,
+}
+
+pub fn list_sinks() -> Result<Vec<Sink>, VolumeError> {
+    let output = exec::read("pactl", &["list", "sinks"])?;
+    Ok(parse_sinks(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_sinks(stdout: &str) -> Vec<Sink> {
+    let mut sinks: Vec<Sink> = parse_list(stdout, "Sink #")
+        .into_iter()
+        .filter_map(|(index, mut props)| {
+            let name = props.remove("Name")?;
+            let description = props.remove("Description").unwrap_or_else(|| name.clone());
+            let volume = props.remove("Volume").unwrap_or_default();
+            let sample_spec = props.remove("Sample Specification").unwrap_or_default();
+            let monitor_source = props.remove("Monitor Source").filter(|source| source != "n/a");
+            Some(Sink {
+                index,
+                name,
+                description,
+                volume,
+                sample_spec,
+                monitor_source,
+            })
+        })
+        .collect();
+    sinks.sort_by_key(|s| s.index);
+    sinks
+}
+
+pub fn set_default_sink(name: &str) -> Result<(), VolumeError> {
+    change("pactl", &["set-default-sink", name])
+}
+
+/// Active profile of every sound card, keyed by card name.
+pub fn card_profiles() -> Result<BTreeMap<String, String>, VolumeError> {
+    let output = exec::read("pactl", &["list", "cards"])?;
+    let profiles = parse_list(&String::from_utf8_lossy(&output.stdout), "Card #")
+        .into_values()
+        .filter_map(|mut props| Some((props.remove("Name")?, props.remove("Active Profile")?)))
+        .collect();
+    Ok(profiles)
+}
+
+pub fn set_card_profile(card: &str, profile: &str) -> Result<(), VolumeError> {
+    change("pactl", &["set-card-profile", card, profile])
+}
+
+/// Sample rate in Hz from a sample spec such as `float32le 2ch 44100Hz`.
+pub fn sample_rate(spec: &str) -> Option<u32> {
+    spec.split_whitespace().find_map(|part| part.strip_suffix("Hz")?.parse().ok())
+}
+
+/// Short name of a `Channel Map:` such as `front-left,front-right`: `MONO`,
+/// `STEREO`, `5.1` or `7.1`, otherwise the channel count (`4CH`).
+pub fn channel_layout(map: &str) -> Option<String> {
+    let channels: Vec<&str> = map.split(',').map(str::trim).filter(|c| !c.is_empty()).collect();
+    let has = |channel| channels.contains(&channel);
+    let name = match channels.len() {
+        0 => return None,
+        1 => "MONO",
+        2 if has("front-left") && has("front-right") => "STEREO",
+        6 if has("lfe") => "5.1",
+        8 if has("lfe") => "7.1",
+        n => return Some(format!("{}CH", n)),
+    };
+    Some(name.to_string())
+}
+
+/// "44.1→48 kHz" when a stream at `stream_spec` is resampled to play on a
+/// sink at `sink_spec`, `None` when the rates match or aren't known.
+pub fn resampling_note(stream_spec: &str, sink_spec: &str) -> Option<String> {
+    let from = sample_rate(stream_spec)?;
+    let to = sample_rate(sink_spec)?;
+    let khz = |hz: u32| format!("{}", hz as f32 / 1000.0);
+    (from != to).then(|| format!("{}→{} kHz", khz(from), khz(to)))
+}
+
+/// Sinks that run at the sample rate of a stream at `stream_spec`, so it could
+/// play there without resampling. The mixer's own helper sinks are left out.
+pub fn same_rate_sinks<'a>(stream_spec: &str, sinks: &'a [Sink]) -> Vec<&'a Sink> {
+    let Some(rate) = sample_rate(stream_spec) else {
+        return Vec::new();
+    };
+    sinks
+        .iter()
+        .filter(|s| !s.name.starts_with(OWN_SINK_PREFIX) && sample_rate(&s.sample_spec) == Some(rate))
+        .collect()
+}
+
+/// Default label order: the tab/page or media title when present, then the
+/// application name, then the binary.
+pub const DEFAULT_LABEL_PROPERTIES: [&str; 3] = ["media.name", "application.name", "application.process.binary"];
+
+/// Tried after the configured order: the PipeWire node name, then the name of
+/// the stream's client (filled in by `name_nameless_streams`).
+const FALLBACK_LABEL_PROPERTIES: [&str; 2] = ["node.name", "client.name"];
+
+/// Name a stream by the first of the `order` properties it has, then by the
+/// fallbacks.
+pub fn resolve_display_name<'a>(props: &'a Props, order: &[String]) -> &'a str {
+    order
+        .iter()
+        .map(String::as_str)
+        .chain(FALLBACK_LABEL_PROPERTIES)
+        .filter_map(|key| props.get(key))
+        .find(|value| !value.is_empty())
+        .map(String::as_str)
+        .unwrap_or("Unknown")
+}
+
+/// A stable key for "the same app" across streams: the application name,
+/// falling back to the binary. Used for anything remembered per app.
+pub fn app_key(props: &Props) -> Option<&str> {
+    props
+        .get("application.name")
+        .or_else(|| props.get("application.process.binary"))
+        .map(String::as_str)
+}
+
+/// Event sounds (beeps, notifications) rather than an app: the `event` media
+/// role, or libcanberra as the client.
+pub fn is_system_sound(props: &Props) -> bool {
+    props.get("media.role").is_some_and(|role| role.eq_ignore_ascii_case("event"))
+        || props.get("application.id").is_some_and(|id| id == "org.freedesktop.libcanberra")
+}
+
+/// The process behind a stream (`application.process.id`), if it says.
+pub fn process_id(props: &Props) -> Option<u32> {
+    props.get("application.process.id")?.trim().parse().ok()
+}
+
+/// The streams (sink-input indices) of process `process_id`, lowest first.
+pub fn sink_inputs_of_process(streams: &HashMap<u32, Props>, process_id: u32) -> Vec<u32> {
+    let mut indices: Vec<u32> = streams
+        .iter()
+        .filter(|(_, props)| self::process_id(props) == Some(process_id))
+        .map(|(index, _)| *index)
+        .collect();
+    indices.sort();
+    indices
+}
+
+/// What happened to an object in a `pactl subscribe` event.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EventKind {
+    New,
+    Change,
+    Remove,
+}
+
+/// One line of `pactl subscribe`, e.g. `Event 'change' on sink-input #42`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SubscribeEvent {
+    pub kind: EventKind,
+    pub facility: String,
+    pub index: u32,
+}
+
+pub fn parse_subscribe_line(line: &str) -> Option<SubscribeEvent> {
+    let rest = line.trim().strip_prefix("Event '")?;
+    let (kind, rest) = rest.split_once("' on ")?;
+    let (facility, index) = rest.split_once(" #")?;
+
+    let kind = match kind {
+        "new" => EventKind::New,
+        "change" => EventKind::Change,
+        "remove" => EventKind::Remove,
+        _ => return None,
+    };
+
+    Some(SubscribeEvent {
+        kind,
+        facility: facility.to_string(),
+        index: index.trim().parse().ok()?,
+    })
+}
+
+/// Whether an event changes the device lists (as opposed to the streams):
+/// a sink, source or card coming, going or changing, or the server's defaults.
+pub fn is_device_event(event: &SubscribeEvent) -> bool {
+    matches!(event.facility.as_str(), "sink" | "source" | "card" | "server")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SINK_INPUTS: &str = "Sink Input #41
+\tDriver: PipeWire
+\tSink: 57
+\tSample Specification: float32le 2ch 44100Hz
+\tChannel Map: front-left,front-right
+\tMute: no
+\tVolume: front-left: 42597 /  65% / -11.23 dB,   front-right: 42597 /  65% / -11.23 dB
+\tSink Latency: 0 usec
+\tProperties:
+\t\tmedia.name = \"Song: Title\"
+\t\tapplication.name = \"Firefox\"
+Sink Input #42
+\tSink: 58
+\tMute: yes
+\tProperties:
+\t\tapplication.process.binary = \"mpv\"
+";
+
+    #[test]
+    fn parses_fields_and_properties_per_entry() {
+        let inputs = parse_sink_inputs_output(SINK_INPUTS);
+
+        assert_eq!(inputs.len(), 2);
+        let firefox = &inputs[&41];
+        assert_eq!(firefox["Sink"], "57");
+        assert_eq!(firefox["Mute"], "no");
+        assert_eq!(firefox["Sample Specification"], "float32le 2ch 44100Hz");
+        assert_eq!(firefox["Channel Map"], "front-left,front-right");
+        assert_eq!(firefox["media.name"], "Song: Title");
+        assert_eq!(firefox["application.name"], "Firefox");
+        assert!(!firefox.contains_key("Sink Latency"));
+        assert!(!firefox.contains_key("application.process.binary"));
+
+        let mpv = &inputs[&42];
+        assert_eq!(mpv["Sink"], "58");
+        assert_eq!(mpv["Mute"], "yes");
+        assert_eq!(mpv["application.process.binary"], "mpv");
+    }
+
+    #[test]
+    fn names_streams_without_name_properties() {
+        let props = |pairs: &[(&str, &str)]| -> Props {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+        let mut streams = HashMap::from([
+            (1, props(&[("Client", "7"), ("application.process.id", "4242")])),
+            (2, props(&[("Client", "8")])),
+            (3, props(&[("media.name", "Song")])),
+            (4, props(&[("node.name", "speaker-test"), ("Client", "7"), ("application.process.id", "4242")])),
+        ]);
+        let clients = HashMap::from([(7, props(&[("application.name", "Game Engine")])), (8, props(&[]))]);
+        fill_missing_names(&mut streams, &clients, |pid| (pid == 4242).then(|| "game.x86_64".to_string()));
+
+        let order: Vec<String> = DEFAULT_LABEL_PROPERTIES.map(String::from).to_vec();
+        assert_eq!(resolve_display_name(&streams[&1], &order), "game.x86_64");
+        assert_eq!(streams[&1]["client.name"], "Game Engine");
+        assert_eq!(app_key(&streams[&1]), Some("game.x86_64"));
+        assert_eq!(resolve_display_name(&streams[&2], &order), "Unknown");
+        assert!(!streams[&3].contains_key("client.name"));
+        // A node name is enough: no client or /proc lookup for it.
+        assert!(!streams[&4].contains_key("client.name"));
+        assert!(!streams[&4].contains_key("application.process.binary"));
+        assert!(has_usual_name(&streams[&4]) && !has_usual_name(&props(&[("Client", "8")])));
+
+        // A bare node name is better than nothing, and comes after the order.
+        let node = props(&[("node.name", "alsa_playback.speaker-test")]);
+        assert_eq!(resolve_display_name(&node, &order), "alsa_playback.speaker-test");
+        assert_eq!(resolve_display_name(&props(&[("client.name", "Game Engine")]), &order), "Game Engine");
+    }
+
+    #[test]
+    fn names_channel_layouts() {
+        assert_eq!(channel_layout("mono").as_deref(), Some("MONO"));
+        assert_eq!(channel_layout("front-left,front-right").as_deref(), Some("STEREO"));
+        assert_eq!(
+            channel_layout("front-left,front-right,front-center,lfe,rear-left,rear-right").as_deref(),
+            Some("5.1")
+        );
+        assert_eq!(
+            channel_layout("front-left,front-right,front-center,lfe,side-left,side-right").as_deref(),
+            Some("5.1")
+        );
+        assert_eq!(channel_layout("front-left,front-right,rear-left,rear-right").as_deref(), Some("4CH"));
+        assert_eq!(channel_layout("aux0,aux1").as_deref(), Some("2CH"));
+        assert_eq!(channel_layout(""), None);
+    }
+
+    #[test]
+    fn recognizes_system_sounds() {
+        let props = |pairs: &[(&str, &str)]| -> Props {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+        assert!(is_system_sound(&props(&[("media.role", "event"), ("application.name", "GNOME Shell")])));
+        assert!(is_system_sound(&props(&[("application.id", "org.freedesktop.libcanberra")])));
+        assert!(!is_system_sound(&props(&[("media.role", "music"), ("application.name", "Spotify")])));
+        assert!(!is_system_sound(&props(&[("application.name", "Firefox")])));
+    }
+
+    #[test]
+    fn sorts_command_failures_into_errors() {
+        use std::io;
+        use std::os::unix::process::ExitStatusExt;
+        use std::process::ExitStatus;
+
+        let output = |code: i32, stdout: &str, stderr: &str| Output {
+            status: ExitStatus::from_raw(code << 8),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        };
+        let missing = io::Error::from(io::ErrorKind::NotFound);
+        assert_eq!(exec::check("wpctl", Err(missing)).unwrap_err(), VolumeError::BinaryNotFound("wpctl".to_string()));
+        assert_eq!(
+            exec::check("pactl", Ok(output(1, "", "Failure: No such entity\n"))).unwrap_err(),
+            VolumeError::CommandFailed { code: Some(1), stderr: "Failure: No such entity\n".to_string() }
+        );
+
+        let sink = "@DEFAULT_AUDIO_SINK@";
+        assert_eq!(node_state(sink, Ok(output(0, "Volume: 0.40 [MUTED]\n", ""))), Ok((40.0, true)));
+        assert_eq!(
+            node_state(sink, Ok(output(0, "Volume: loud\n", ""))),
+            Err(VolumeError::ParseError("Volume: loud".to_string()))
+        );
+        let none = exec::check("wpctl", Ok(output(1, "", "Translate ID error: default node not found\n")));
+        assert_eq!(node_state(sink, none.clone()), Err(VolumeError::NoDefaultSink));
+        assert!(matches!(node_state("@DEFAULT_AUDIO_SOURCE@", none), Err(VolumeError::CommandFailed { .. })));
+        assert_eq!(node_state(sink, Err(VolumeError::Timeout)), Err(VolumeError::Timeout));
+    }
+
+    #[test]
+    fn parses_every_channel_of_a_volume() {
+        let channels =
+            parse_channel_volumes("front-left: 42597 /  65% / -11.23 dB,   front-right: 0 /   0% / -inf dB");
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[0].channel, "front-left");
+        assert_eq!((channels[0].raw, channels[0].percent, channels[0].db), (42597, 65.0, -11.23));
+        assert_eq!(channels[1].db, f32::NEG_INFINITY);
+        assert_eq!(parse_channel_volumes("mono: 65536 / 100% / 0.00 dB")[0].raw, 65536);
+        assert!(parse_channel_volumes("").is_empty());
+    }
+
+    #[test]
+    fn maps_streams_to_processes_and_back() {
+        let props = |pairs: &[(&str, &str)]| -> Props {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+        let streams = HashMap::from([
+            (12, props(&[("application.name", "Firefox"), ("application.process.id", "4242")])),
+            (7, props(&[("application.name", "Firefox"), ("application.process.id", "4242")])),
+            (9, props(&[("application.name", "mpv"), ("application.process.id", "777")])),
+            (3, props(&[("application.name", "Game")])),
+            (5, props(&[("application.process.id", "n/a")])),
+        ]);
+
+        assert_eq!(process_id(&streams[&12]), Some(4242));
+        assert_eq!(process_id(&streams[&3]), None);
+        assert_eq!(process_id(&streams[&5]), None);
+        assert_eq!(sink_inputs_of_process(&streams, 4242), [7, 12]);
+        assert_eq!(sink_inputs_of_process(&streams, 777), [9]);
+        assert!(sink_inputs_of_process(&streams, 1).is_empty());
+    }
+
+    #[test]
+    fn picks_the_most_limiting_backend_mode() {
+        assert_eq!(BackendMode::detect(true, None, true), BackendMode::Full);
+        assert_eq!(BackendMode::detect(true, None, false), BackendMode::NoWpctl);
+        assert_eq!(BackendMode::detect(true, Some("tcp:nas"), false), BackendMode::Remote("tcp:nas".to_string()));
+        assert_eq!(BackendMode::detect(false, Some("tcp:nas"), true), BackendMode::Unreachable);
+        assert!(BackendMode::Full.badge().is_none());
+        assert!(BackendMode::NoWpctl.badge().is_some_and(|(_, detail)| detail.contains("wpctl")));
+    }
+
+    #[test]
+    fn parses_clock_settings() {
+        let text = "Found \"settings\" metadata 31
+update: id:0 key:'log.level' value:'2' type:''
+update: id:0 key:'clock.rate' value:'48000' type:''
+update: id:0 key:'clock.quantum' value:'1024' type:''
+update: id:0 key:'clock.force-quantum' value:'0' type:''
+";
+        let settings = parse_clock_settings(text);
+        assert_eq!(settings.rate, Some(48000));
+        assert_eq!(settings.quantum, Some(1024));
+        assert_eq!(settings.forced_quantum, None);
+
+        let forced = parse_clock_settings("update: id:0 key:'clock.force-quantum' value:'256' type:''");
+        assert_eq!(forced.forced_quantum, Some(256));
+    }
+
+    #[test]
+    fn finds_sinks_at_the_stream_rate() {
+        let sink = |index: u32, name: &str, spec: &str| Sink {
+            index,
+            name: name.to_string(),
+            description: name.to_string(),
+            volume: String::new(),
+            sample_spec: spec.to_string(),
+            monitor_source: None,
+        };
+        let sinks = [
+            sink(57, "alsa_output.hdmi", "s32le 2ch 48000Hz"),
+            sink(58, "alsa_output.usb-dac", "s24le 2ch 44100Hz"),
+            sink(59, "k_volume_mixer_eq_bass", "float32le 2ch 44100Hz"),
+        ];
+
+        let matching = same_rate_sinks("float32le 2ch 44100Hz", &sinks);
+        assert_eq!(matching.len(), 1);
+        assert_eq!(matching[0].index, 58);
+        assert!(same_rate_sinks("float32le 2ch", &sinks).is_empty());
+    }
+
+    #[test]
+    fn finds_the_monitor_of_a_sink() {
+        let sinks = "Sink #58
+\tState: RUNNING
+\tName: alsa_output.usb-dac
+\tDescription: USB DAC
+\tMonitor Source: alsa_output.usb-dac.monitor
+Sink #59
+\tName: k_volume_mixer_all_12
+\tMonitor Source: n/a
+";
+        let sinks = parse_sinks(sinks);
+        assert_eq!(sinks[0].monitor_source.as_deref(), Some("alsa_output.usb-dac.monitor"));
+        assert_eq!(sinks[1].monitor_source, None);
+    }
+
+    const WPCTL_STATUS: &str = "PipeWire 'pipewire-0' [1.0.5, user@desk, cookie:1234567]
+ └─ Clients:
+        31. WirePlumber                         [1.0.5, user@desk, pid:1042]
+
+Audio
+ ├─ Devices:
+ │      42. Built-in Audio                      [alsa]
+ │  
+ ├─ Sinks:
+ │  *   47. Built-in Audio Analog Stereo        [vol: 0.40]
+ │      58. HDMI / DisplayPort 1 Output         [vol: 1.00 MUTED]
+ │  
+ ├─ Sink endpoints:
+ │  
+ ├─ Sources:
+ │  *   48. Built-in Audio Analog Stereo        [vol: 0.74]
+ │  
+ ├─ Source endpoints:
+ │  
+ └─ Streams:
+        67. Firefox
+             68. output_FL       > Built-in Audio:playback_FL	[active]
+
+Video
+ ├─ Devices:
+ │      50. Integrated Camera                   [v4l2]
+ │  
+ ├─ Sinks:
+ │  
+ ├─ Sources:
+ │  *   52. Integrated Camera (V4L2)
+ │  
+ └─ Streams:
+
+Settings
+ └─ Default Configured Node Names:
+         0. Audio/Sink    alsa_output.pci-0000_00_1f.3.analog-stereo
+";
+
+    #[test]
+    fn parses_wpctl_status_tree() {
+        let status = parse_wpctl_status(WPCTL_STATUS);
+
+        assert_eq!(status.sinks.len(), 2);
+        assert_eq!(
+            status.sinks[0],
+            Node {
+                id: 47,
+                name: "Built-in Audio Analog Stereo".to_string(),
+                volume: Some(40.0),
+                muted: false,
+                is_default: true,
+            }
+        );
+        assert_eq!(status.sinks[1].id, 58);
+        assert_eq!(status.sinks[1].name, "HDMI / DisplayPort 1 Output");
+        assert!(status.sinks[1].muted);
+        assert!(!status.sinks[1].is_default);
+
+        // The camera is a video source and must not show up.
+        assert_eq!(status.sources.len(), 1);
+        assert_eq!(status.default_source().map(|n| n.id), Some(48));
+        assert!((status.sources[0].volume.unwrap() - 74.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn wpctl_status_without_audio_is_empty() {
+        assert_eq!(parse_wpctl_status(""), WpctlStatus::default());
+        assert_eq!(parse_wpctl_status("Video\n ├─ Sinks:\n │  *   50. Camera\n"), WpctlStatus::default());
+    }
+
+    #[test]
+    fn ignores_lines_before_the_first_entry() {
+        let inputs = parse_sink_inputs_output("Mute: no\nfoo = \"bar\"\n");
+        assert!(inputs.is_empty());
+    }
+
+    #[test]
+    fn picks_out_device_events() {
+        let is_device = |line| parse_subscribe_line(line).as_ref().is_some_and(is_device_event);
+        assert!(is_device("Event 'new' on sink #61"));
+        assert!(is_device("Event 'change' on card #44"));
+        assert!(is_device("Event 'change' on server #4294967295"));
+        assert!(!is_device("Event 'change' on sink-input #42"));
+        assert!(!is_device("Event 'remove' on source-output #9"));
+    }
+}
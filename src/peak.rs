@@ -0,0 +1,217 @@
+//! Per-stream peak meters. Each sink input gets a `parec --monitor-stream`
+//! process that samples its output at a low rate; a reader thread folds the
+//! samples into one peak per tick. `SourceMeter` does the same for a whole
+//! source, such as a sink's monitor.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufReader, Read};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::pactl::Sink;
+
+/// Samples per second requested from `parec`. Plenty for a meter, and cheap.
+const SAMPLE_RATE: u32 = 100;
+/// Samples folded into one reported peak (100 ms).
+const SAMPLES_PER_TICK: usize = 10;
+/// How many ticks of history to keep per stream (10 s).
+const HISTORY_TICKS: usize = 100;
+
+/// Peaks below this are treated as silence and never normalized against.
+const SILENCE: f32 = 0.001;
+
+type Shared = Arc<Mutex<HashMap<u32, f32>>>;
+
+pub struct PeakMeters {
+    latest: Shared,
+    meters: HashMap<u32, Child>,
+    history: HashMap<u32, VecDeque<f32>>,
+}
+
+impl PeakMeters {
+    pub fn new() -> Self {
+        Self {
+            latest: Arc::new(Mutex::new(HashMap::new())),
+            meters: HashMap::new(),
+            history: HashMap::new(),
+        }
+    }
+
+    /// Start meters for new streams and stop them for vanished ones.
+    /// `streams` maps each sink-input index to the sink it plays on.
+    pub fn sync(&mut self, streams: &HashMap<u32, String>, sinks: &[Sink]) {
+        self.meters.retain(|index, child| {
+            let keep = streams.contains_key(index);
+            if !keep {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            keep
+        });
+        self.history.retain(|index, _| streams.contains_key(index));
+        if let Ok(mut latest) = self.latest.lock() {
+            latest.retain(|index, _| streams.contains_key(index));
+        }
+
+        for (index, sink) in streams {
+            if self.meters.contains_key(index) {
+                continue;
+            }
+            let Some(sink) = sink.parse::<u32>().ok().and_then(|s| sinks.iter().find(|k| k.index == s)) else {
+                continue;
+            };
+            let device = format!("{}.monitor", sink.name);
+            if let Some(child) = spawn_meter(*index, &device, Some(*index), Arc::clone(&self.latest)) {
+                self.meters.insert(*index, child);
+            }
+        }
+    }
+
+    /// Move the latest measured peaks into the per-stream history and return
+    /// them (0.0..=1.0, linear).
+    pub fn poll(&mut self) -> HashMap<u32, f32> {
+        let latest = match self.latest.lock() {
+            Ok(mut latest) => std::mem::take(&mut *latest),
+            Err(_) => return HashMap::new(),
+        };
+        for (index, peak) in &latest {
+            let history = self.history.entry(*index).or_default();
+            if history.len() == HISTORY_TICKS {
+                history.pop_front();
+            }
+            history.push_back(*peak);
+        }
+        latest
+    }
+
+    /// Highest peak seen for `index` over the kept history.
+    pub fn recent_peak(&self, index: u32) -> Option<f32> {
+        let history = self.history.get(&index)?;
+        history.iter().copied().reduce(f32::max)
+    }
+
+    /// Forget the measured history of `index`, e.g. after changing its volume
+    /// so that stale peaks aren't normalized against twice.
+    pub fn clear_history(&mut self, index: u32) {
+        self.history.remove(&index);
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.meters.is_empty()
+    }
+
+    pub fn stop_all(&mut self) {
+        for (_, mut child) in self.meters.drain() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Level meter on one whole source. Used for the default sink's monitor, i.e.
+/// everything playing on the output.
+pub struct SourceMeter {
+    /// The sink being watched and the name of its monitor source.
+    watching: Option<(String, String)>,
+    child: Option<Child>,
+    latest: Shared,
+}
+
+impl SourceMeter {
+    pub fn new() -> Self {
+        Self {
+            watching: None,
+            child: None,
+            latest: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn sink(&self) -> Option<&str> {
+        self.watching.as_ref().map(|(sink, _)| sink.as_str())
+    }
+
+    pub fn source(&self) -> Option<&str> {
+        self.watching.as_ref().map(|(_, source)| source.as_str())
+    }
+
+    /// Measure `source`, the monitor of `sink`, replacing any previous meter.
+    pub fn watch(&mut self, sink: &str, source: &str) {
+        self.stop();
+        self.child = spawn_meter(0, source, None, Arc::clone(&self.latest));
+        self.watching = Some((sink.to_string(), source.to_string()));
+    }
+
+    /// Peak (0.0..=1.0, linear) measured since the last poll, if any.
+    pub fn poll(&mut self) -> Option<f32> {
+        self.latest.lock().ok()?.remove(&0)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.child.is_some()
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        self.watching = None;
+    }
+}
+
+/// Volume (percent) that would bring a stream whose recent peak is `peak` at
+/// `current` percent up or down to `target` peak, clamped to `max` percent.
+/// pactl percentages are cubic, so the linear gain ratio goes through a cube
+/// root. Returns `None` when there's nothing meaningful to measure.
+pub fn normalized_volume(current: f32, peak: f32, target: f32, max: f32) -> Option<f32> {
+    if peak < SILENCE || current <= 0.0 {
+        return None;
+    }
+    Some((current * (target / peak).cbrt()).clamp(0.0, max))
+}
+
+/// Record `device` (only sink input `stream` of it, if given) and report its
+/// peaks into `latest` under `index`.
+fn spawn_meter(index: u32, device: &str, stream: Option<u32>, latest: Shared) -> Option<Child> {
+    let mut command = Command::new("parec");
+    command.args(["--device", device]);
+    if let Some(stream) = stream {
+        command.args(["--monitor-stream", &stream.to_string()]);
+    }
+    let mut child = command
+        .args([
+            "--format=float32le",
+            "--channels=1",
+            &format!("--rate={}", SAMPLE_RATE),
+            "--latency-msec=100",
+            "--client-name=k_volume_mixer",
+            "--stream-name=peak detect",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    let stdout = child.stdout.take()?;
+    thread::spawn(move || {
+        let mut reader = BufReader::new(stdout);
+        let mut buf = [0u8; 4 * SAMPLES_PER_TICK];
+        // Ends when the process is killed and the pipe closes.
+        while reader.read_exact(&mut buf).is_ok() {
+            let peak = buf
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]).abs())
+                .fold(0.0, f32::max);
+            match latest.lock() {
+                Ok(mut latest) => {
+                    let entry = latest.entry(index).or_insert(0.0);
+                    *entry = entry.max(peak.min(1.0));
+                }
+                Err(_) => break,
+            }
+        }
+    });
+
+    Some(child)
+}
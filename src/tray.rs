@@ -0,0 +1,129 @@
+//! StatusNotifierItem tray icon (via `ksni`, pure D-Bus, no GTK).
+//!
+//! The tray lives on ksni's own thread; clicks and menu picks are sent to the
+//! UI as `TrayEvent`s, with a repaint request so they are seen promptly.
+
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+use eframe::egui;
+use ksni::blocking::TrayMethods;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TrayEvent {
+    /// The icon was clicked at these screen coordinates (physical pixels).
+    Activate { x: i32, y: i32 },
+    ShowWindow,
+    Quit,
+}
+
+pub struct Tray {
+    handle: Option<ksni::blocking::Handle<Icon>>,
+    rx: Receiver<TrayEvent>,
+    status: String, // last tooltip text sent
+}
+
+impl Tray {
+    pub fn new(ctx: &egui::Context) -> Self {
+        let (tx, rx) = channel();
+        let icon = Icon {
+            tx,
+            ctx: ctx.clone(),
+            status: String::new(),
+        };
+        let handle = icon
+            .spawn()
+            .map_err(|e| eprintln!("Tray icon unavailable: {}", e))
+            .ok();
+        Self {
+            handle,
+            rx,
+            status: String::new(),
+        }
+    }
+
+    pub fn available(&self) -> bool {
+        self.handle.as_ref().is_some_and(|h| !h.is_closed())
+    }
+
+    pub fn poll(&self) -> Vec<TrayEvent> {
+        self.rx.try_iter().collect()
+    }
+
+    /// Show `status` as the icon's tooltip. Only an actual change goes out
+    /// over D-Bus.
+    pub fn set_status(&mut self, status: String) {
+        if status == self.status {
+            return;
+        }
+        if let Some(handle) = &self.handle {
+            let text = status.clone();
+            handle.update(move |icon| icon.status = text);
+        }
+        self.status = status;
+    }
+
+    pub fn shutdown(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.shutdown().wait();
+        }
+    }
+}
+
+struct Icon {
+    tx: Sender<TrayEvent>,
+    ctx: egui::Context,
+    status: String,
+}
+
+impl Icon {
+    fn send(&self, event: TrayEvent) {
+        let _ = self.tx.send(event);
+        self.ctx.request_repaint();
+    }
+}
+
+impl ksni::Tray for Icon {
+    fn id(&self) -> String {
+        "k_volume_mixer".into()
+    }
+
+    fn title(&self) -> String {
+        "K Volume Mixer".into()
+    }
+
+    fn icon_name(&self) -> String {
+        "audio-volume-high".into()
+    }
+
+    fn tool_tip(&self) -> ksni::ToolTip {
+        ksni::ToolTip {
+            title: "K Volume Mixer".into(),
+            description: self.status.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn activate(&mut self, x: i32, y: i32) {
+        self.send(TrayEvent::Activate { x, y });
+    }
+
+    fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
+        use ksni::menu::StandardItem;
+        vec![
+            StandardItem {
+                label: "Open mixer".into(),
+                activate: Box::new(|this: &mut Self| this.send(TrayEvent::ShowWindow)),
+                ..Default::default()
+            }
+            .into(),
+            ksni::MenuItem::Separator,
+            StandardItem {
+                label: "Quit".into(),
+                icon_name: "application-exit".into(),
+                activate: Box::new(|this: &mut Self| this.send(TrayEvent::Quit)),
+                ..Default::default()
+            }
+            .into(),
+        ]
+    }
+}
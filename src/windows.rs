@@ -0,0 +1,125 @@
+//! Window titles of the processes playing audio, from the window manager
+//! (`wmctrl -lp` for the list, `xprop -root _NET_ACTIVE_WINDOW` for focus).
+//!
+//! Opt-in: the background thread only queries while enabled, every
+//! `POLL_INTERVAL`. X11 (or XWayland windows) only; elsewhere there are
+//! simply no titles.
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+pub struct WindowTitles {
+    enabled: Arc<AtomicBool>,
+    titles: Arc<Mutex<HashMap<u32, String>>>, // process id -> title
+}
+
+impl WindowTitles {
+    pub fn new(enabled: bool) -> Self {
+        let enabled = Arc::new(AtomicBool::new(enabled));
+        let titles = Arc::new(Mutex::new(HashMap::new()));
+        {
+            let enabled = Arc::clone(&enabled);
+            let titles = Arc::clone(&titles);
+            thread::spawn(move || loop {
+                let found = if enabled.load(Ordering::Relaxed) { query() } else { HashMap::new() };
+                match titles.lock() {
+                    Ok(mut titles) => *titles = found,
+                    Err(_) => break,
+                }
+                thread::sleep(POLL_INTERVAL);
+            });
+        }
+        Self { enabled, titles }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Title of process `pid`'s window: the focused one if it has several.
+    pub fn for_process(&self, pid: u32) -> Option<String> {
+        self.titles.lock().ok()?.get(&pid).cloned()
+    }
+}
+
+fn query() -> HashMap<u32, String> {
+    let Ok(list) = Command::new("wmctrl").arg("-lp").output() else {
+        return HashMap::new(); // not installed
+    };
+    let active = Command::new("xprop")
+        .args(["-root", "_NET_ACTIVE_WINDOW"])
+        .output()
+        .ok()
+        .and_then(|out| parse_active_window(&String::from_utf8_lossy(&out.stdout)));
+    titles_by_process(&String::from_utf8_lossy(&list.stdout), active)
+}
+
+/// One title per process from `wmctrl -lp` lines
+/// (`<id> <desktop> <pid> <host> <title>`), preferring window `active`.
+fn titles_by_process(list: &str, active: Option<u64>) -> HashMap<u32, String> {
+    let mut titles = HashMap::new();
+    for line in list.lines() {
+        let mut rest = line;
+        let (Some(id), Some(_desktop), Some(pid), Some(_host)) =
+            (field(&mut rest), field(&mut rest), field(&mut rest), field(&mut rest))
+        else {
+            continue;
+        };
+        let title = rest.trim(); // may itself contain runs of spaces
+        let (Some(id), Ok(pid)) = (parse_window_id(id), pid.parse::<u32>()) else {
+            continue;
+        };
+        if pid == 0 || title.is_empty() {
+            continue;
+        }
+        if Some(id) == active || !titles.contains_key(&pid) {
+            titles.insert(pid, title.to_string());
+        }
+    }
+    titles
+}
+
+/// Split the next whitespace-separated field off the front of `rest`.
+fn field<'a>(rest: &mut &'a str) -> Option<&'a str> {
+    let (field, tail) = rest.trim_start().split_once(char::is_whitespace)?;
+    *rest = tail;
+    Some(field)
+}
+
+/// `_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00003`.
+fn parse_active_window(text: &str) -> Option<u64> {
+    parse_window_id(text.rsplit('#').next()?.trim())
+}
+
+fn parse_window_id(id: &str) -> Option<u64> {
+    u64::from_str_radix(id.trim().strip_prefix("0x")?, 16).ok().filter(|id| *id != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_the_focused_window_of_a_process() {
+        let list = "0x03a00003  0 4242   desk Mozilla Firefox
+0x03a0001b  0 4242   desk Lofi  beats - YouTube — Mozilla Firefox
+0x04200007 -1 0      desk Desktop
+0x05000004  0 777    desk mpv
+";
+        let active = parse_active_window("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a0001b\n");
+
+        let titles = titles_by_process(list, active);
+        assert_eq!(titles.len(), 2);
+        assert_eq!(titles[&4242], "Lofi  beats - YouTube — Mozilla Firefox");
+        assert_eq!(titles[&777], "mpv");
+
+        let titles = titles_by_process(list, None);
+        assert_eq!(titles[&4242], "Mozilla Firefox");
+    }
+}